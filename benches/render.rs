@@ -0,0 +1,50 @@
+// Times rendering a timeline with a hex grid colored by position, the evaluation hot path:
+// cargo bench --bench render [--features parallel]
+use std::time::{Duration, Instant};
+
+const FRAMES: u32 = 30;
+
+// one second of a position field drawn through a hex grid of small tiles straight to the output
+fn timeline(orientation: &str) -> String {
+    format!(r#"{{
+        "settings": {{"width": 640, "height": 480, "anti_alias": true, "fps": {FRAMES}}},
+        "blocks": [{{"duration": 1000, "transition": 0, "graph": {{
+            "nodes": [
+                {{"type": "output"}},
+                {{"type": "position"}},
+                {{"type": "float", "value": 3.0}},
+                {{"type": "hex", "orientation": "{orientation}", "anti_alias": true}}
+            ],
+            "links": [
+                {{"from": {{"node": 1, "pin": 0}}, "to": {{"node": 3, "pin": 0}}}},
+                {{"from": {{"node": 2, "pin": 0}}, "to": {{"node": 3, "pin": 1}}}},
+                {{"from": {{"node": 2, "pin": 0}}, "to": {{"node": 3, "pin": 2}}}},
+                {{"from": {{"node": 3, "pin": 0}}, "to": {{"node": 0, "pin": 0}}}}
+            ]
+        }}}}]
+    }}"#)
+}
+
+fn report(name: &str, frames: u32, elapsed: Duration) {
+    assert_eq!(frames, FRAMES);
+    println!("{:<24} {:>8.2} ms per frame", name, elapsed.as_secs_f64() * 1000.0 / frames as f64);
+}
+
+fn main() {
+    let directory = std::env::temp_dir().join(format!("pixellab-bench-{}", std::process::id()));
+    for orientation in ["pointy", "flat"] {
+        let raw = timeline(orientation);
+        let start = Instant::now();
+        let frames = pixellab::evaluate_timeline(&raw).unwrap();
+        report(&format!("{} hex", orientation), frames, start.elapsed());
+
+        // the same frames written as png files
+        let input = directory.with_extension(format!("{}.json", orientation));
+        std::fs::write(&input, raw).unwrap();
+        let start = Instant::now();
+        let frames = pixellab::render_timeline(&input, directory.clone(), None, None).unwrap();
+        report(&format!("{} hex to png", orientation), frames, start.elapsed());
+        std::fs::remove_file(&input).unwrap();
+    }
+    std::fs::remove_dir_all(&directory).unwrap();
+}
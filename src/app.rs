@@ -1,10 +1,10 @@
-use std::{f32::consts::TAU, path::PathBuf, sync::Arc};
+use std::{collections::hash_map::DefaultHasher, f32::consts::TAU, fmt::Write, hash::{Hash, Hasher}, path::PathBuf, sync::Arc};
 
-use egui::{Color32, ColorImage, ImageData, Response, Sense, Stroke, TextureHandle, TextureOptions, Ui, Vec2, Widget};
+use egui::{Color32, ColorImage, ImageData, Key, KeyboardShortcut, Modifiers, Pos2, Response, Sense, Stroke, TextureHandle, TextureOptions, Ui, Vec2, Widget};
 use json::JsonValue;
-use tiny_skia::{Color, Pixmap, PremultipliedColorU8, Transform};
+use tiny_skia::{Color, Pixmap, PixmapPaint, PremultipliedColorU8, Transform};
 
-use crate::{fields::{ConstantField, Field2}, hex::{draw_hex_grid, HexGrid}, nodes::node::{Graph, NodeWidget, Pin, PinDirection, PinId}, time::{Duration, Instant}, tweening};
+use crate::{fields::{rasterize, rasterize_tiled, AddressMode, BlendField, BlendMode, ConstantField, Field2, GammaField, GrayscaleField, MirrorAxes, MirrorField, OpacityField, PolarField, PositionField, SampledField, Sampling, TileField, VignetteField, TILE_SIZE}, filters, palette, hex::{draw_hex_grid, hex_grid_svg, HexGrid, Orientation}, images, nodes::{bezier::{Bezier, ARCLEN_SAMPLES}, node::{Graph, Node, NodeWidget, Pin, PinDirection, PinId, PinType}, spline::CatmullRom}, time::{Duration, Instant}, tweening::{self, Waveform}};
 
 impl Field2<Color> for Pixmap {
     fn at(&self, position: tiny_skia::Point) -> Color {
@@ -16,6 +16,17 @@ impl Field2<Color> for Pixmap {
     }
 }
 
+// wraps the color field of a value, pixmaps are sampled back into pixmaps of the same size
+fn wrap_color_field(value: PinValue, wrap: impl FnOnce(Box<dyn Field2<Color>>) -> Box<dyn Field2<Color>>) -> PinValue {
+    match value {
+        PinValue::Pixmap(pixmap) => {
+            let (width, height) = (pixmap.width(), pixmap.height());
+            rasterize(wrap(Box::new(pixmap)).as_ref(), width, height).map_or(PinValue::None, PinValue::Pixmap)
+        },
+        value => PinValue::ColorField(wrap(value.as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT))))),
+    }
+}
+
 struct TransformedColorField {
     field: Box<dyn Field2<Color>>,
     transform: Transform,
@@ -26,6 +37,40 @@ impl Field2<Color> for TransformedColorField {
         self.transform.map_point(&mut p);
         self.field.at(p)
     }
+    fn constant(&self) -> Option<Color> {
+        self.field.constant()
+    }
+}
+
+// how an image is scaled to the output
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+enum FitMode {
+    // the whole image is visible
+    Contain,
+    // the whole output is covered
+    Cover,
+    Stretch,
+}
+impl FitMode {
+    const ALL: [FitMode; 3] = [FitMode::Contain, FitMode::Cover, FitMode::Stretch];
+    fn name(&self) -> &'static str {
+        match self {
+            FitMode::Contain => "contain",
+            FitMode::Cover => "cover",
+            FitMode::Stretch => "stretch",
+        }
+    }
+    // maps output positions to image positions, both centered
+    fn transform(&self, image: (u32, u32), output: (u32, u32)) -> Transform {
+        let sx = image.0 as f32 / output.0 as f32;
+        let sy = image.1 as f32 / output.1 as f32;
+        match self {
+            FitMode::Contain => Transform::from_scale(sx.max(sy), sx.max(sy)),
+            FitMode::Cover => Transform::from_scale(sx.min(sy), sx.min(sy)),
+            FitMode::Stretch => Transform::from_scale(sx, sy),
+        }
+    }
 }
 
 struct Lerp<T> {
@@ -42,19 +87,17 @@ impl Lerp<f32> {
 enum PinValue {
     None,
     Float(f32),
+    Vec2(Vec2),
     String(String),
     Color(Color),
     Transform(Transform),
     Pixmap(Pixmap),
     ColorField(Box<dyn Field2<Color>>),
+    Palette(Vec<Color>),
 }
 impl PinValue {
-    fn pixmap(self) -> Pixmap {
-        if let PinValue::Pixmap(pixmap) = self {
-            pixmap
-        } else {
-            panic!("Unexpected pin value")
-        }
+    fn pixmap(self) -> Option<Pixmap> {
+        if let PinValue::Pixmap(pixmap) = self { Some(pixmap) } else { None }
     }
     // try to convert value into a color field
     fn as_color_field(self) -> Option<Box<dyn Field2<Color>>> {
@@ -65,48 +108,105 @@ impl PinValue {
             _ => None,
         }
     }
+    fn string(self) -> Option<String> {
+        if let PinValue::String(value) = self { Some(value) } else { None }
+    }
     fn color(self) -> Option<Color> {
         if let PinValue::Color(color) = self { Some(color) } else { None }
     }
     fn f32(self) -> Option<f32> {
         if let PinValue::Float(value) = self { Some(value) } else { None }
     }
+    fn vec2(self) -> Option<Vec2> {
+        if let PinValue::Vec2(value) = self { Some(value) } else { None }
+    }
     fn transform(self) -> Option<Transform> {
         if let PinValue::Transform(value) = self { Some(value) } else { None }
     }
+    fn palette(self) -> Option<Vec<Color>> {
+        if let PinValue::Palette(colors) = self { Some(colors) } else { None }
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 enum NodeType {
     // data types
     Time,
     Float(f32),
+    Vec2(Vec2),
     String(String),
     Color(Color32),
+    ParseColor,
+    SplitRGBA,
+    CombineRGBA,
+    Random(u32),
+    Switch,
+    Palette(PathBuf),
     // tweens
     Lerp,
     Cubic(bool),
+    Wave(Waveform),
+    PingPong,
+    Offset(bool),
+    Remap,
+    Smoothstep,
+    Step,
+    Abs,
+    Sign,
+    Spline(usize),
     // color fields
-    Pixmap(PathBuf),
+    Pixmap(PathBuf, Sampling, AddressMode),
     TransformColorField,
+    Polar,
+    Mirror(MirrorAxes),
+    Tile,
+    Blend(BlendMode),
+    Position,
+    Rasterize,
     // transforms
     Revolution,
     Rotate,
     Scale,
-    Hex,
+    // orient to path, constant speed
+    Path(bool, bool),
+    Skew,
+    Fit(FitMode),
+    // orientation, anti-aliased
+    Hex(Orientation, bool),
+    // filters
+    Pixelate,
+    EdgeDetect,
+    Displace(AddressMode),
+    Gamma,
+    Opacity,
+    Grayscale,
+    Vignette,
+    Scanlines,
+    ChromaticAberration,
+    Bloom,
+    Crop,
+    Background,
+    Quantize,
+    Comment(String),
     Output,
 }
 
 impl NodeType {
-    fn evaluate(&self, pin_values: Vec<PinValue>, pin_index: usize, t: f32) -> PinValue {
+    // produces the value of output pin_index, numbered from 0 in out_pins order.
+    // problems that don't stop evaluation are reported in errors
+    fn evaluate(&self, pin_values: Vec<PinValue>, pin_index: usize, t: f32, settings: &VideoSettings, errors: &mut Vec<String>) -> PinValue {
         let mut pins = pin_values.into_iter();
         match self {
             NodeType::Time => PinValue::Float(t),
             NodeType::Float(value) => PinValue::Float(*value),
+            NodeType::Vec2(value) => PinValue::Vec2(*value),
             NodeType::String(value) => PinValue::String(value.clone()),
-            NodeType::Color(value) => PinValue::Color(Color::from_rgba8(
-                value.r(), value.g(), value.b(), value.a())
-            ),
+            NodeType::Color(value) => {
+                // egui colors are premultiplied
+                let [r, g, b, a] = value.to_srgba_unmultiplied();
+                PinValue::Color(Color::from_rgba8(r, g, b, a))
+            },
             NodeType::Lerp => {
                 // TODO: Handle colors, positions, etc
                 let a = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
@@ -118,7 +218,20 @@ impl NodeType {
                 let value = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
                 PinValue::Float(tweening::cubic_in(value))
             },
-            NodeType::Pixmap(path) => PinValue::Pixmap(Pixmap::load_png(path.as_path()).unwrap()),
+            NodeType::Pixmap(path, sampling, address_mode) => {
+                // a missing or broken file renders transparent, the node shows the error
+                let pixmap = images::load(path).unwrap_or_else(|error| {
+                    if !path.as_os_str().is_empty() {
+                        errors.push(error);
+                    }
+                    settings.empty_pixmap()
+                });
+                // the second output samples the pixmap as a field
+                match pin_index {
+                    1 => PinValue::ColorField(Box::new(SampledField::new(pixmap, *sampling, *address_mode))),
+                    _ => PinValue::Pixmap(pixmap),
+                }
+            },
             NodeType::TransformColorField => {
                 let color = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
                 let transform = pins.next().unwrap_or(PinValue::None).transform().unwrap_or(Transform::identity());
@@ -137,52 +250,446 @@ impl NodeType {
                 let sy = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(sx);
                 PinValue::Transform(Transform::post_scale(&Transform::identity(), sx, sy))
             },
-            NodeType::Hex => {
-                // extract inputs
-                let color = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
-                let spacing = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(8.0);
-                let size = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(8.0);
-                let transform = pins.next().unwrap_or(PinValue::None).transform().unwrap_or(Transform::identity());
-                
-                let mut pixmap = Pixmap::new(320, 200).unwrap();
-                let grid = HexGrid::new(spacing, size, transform.post_translate(160.0, 120.0));
-                
-                draw_hex_grid(&mut pixmap, &grid, color.as_ref());
+            NodeType::Hex(orientation, anti_alias) => {
+                let (grid, color, (width, height)) = hex_grid(pins, *orientation, *anti_alias, settings);
+                let mut pixmap = Pixmap::new(width, height).unwrap();
+                if let Err(error) = draw_hex_grid(&mut pixmap, &grid, color.as_ref()) {
+                    errors.push(error);
+                }
                 PinValue::Pixmap(pixmap)
             },
-            NodeType::Output => pins.next().unwrap_or(PinValue::None),
+            NodeType::Wave(waveform) => {
+                let phase = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let frequency = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                let amplitude = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                PinValue::Float(amplitude * waveform.eval(frequency * phase))
+            },
+            NodeType::PingPong => {
+                let t = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                PinValue::Float(1.0 - (1.0 - 2.0 * t).abs())
+            },
+            NodeType::Offset(wrap) => {
+                let value = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let delay = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let shifted = value + delay;
+                PinValue::Float(if *wrap { shifted.rem_euclid(1.0) } else { shifted })
+            },
+            NodeType::Remap => {
+                let value = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let in_min = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let in_max = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                let out_min = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let out_max = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                PinValue::Float(tweening::remap(value, in_min, in_max, out_min, out_max))
+            },
+            NodeType::Smoothstep => {
+                let edge0 = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let edge1 = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                let x = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                PinValue::Float(tweening::smoothstep(edge0, edge1, x))
+            },
+            NodeType::Pixelate => {
+                let pixmap = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let block_size = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(4.0);
+                PinValue::Pixmap(filters::pixelate(&pixmap, block_size.max(1.0) as u32))
+            },
+            NodeType::EdgeDetect => {
+                let pixmap = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let threshold = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                PinValue::Pixmap(filters::edge_detect(&pixmap, threshold))
+            },
+            NodeType::Polar => {
+                let field = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
+                let repeat = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(6.0);
+                let twist = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                PinValue::ColorField(Box::new(PolarField::new(field, repeat, twist)))
+            },
+            NodeType::Displace(address_mode) => {
+                let source = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let displacement = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let strength = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(8.0);
+                PinValue::Pixmap(filters::displace(source, &displacement, strength, *address_mode))
+            },
+            NodeType::Gamma => {
+                let value = pins.next().unwrap_or(PinValue::None);
+                let gamma = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(2.2);
+                wrap_color_field(value, |field| Box::new(GammaField::new(field, gamma)))
+            },
+            NodeType::Opacity => {
+                let value = pins.next().unwrap_or(PinValue::None);
+                let opacity = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                wrap_color_field(value, |field| Box::new(OpacityField::new(field, opacity)))
+            },
+            NodeType::Grayscale => {
+                let value = pins.next().unwrap_or(PinValue::None);
+                wrap_color_field(value, |field| Box::new(GrayscaleField::new(field)))
+            },
+            NodeType::Vignette => {
+                let value = pins.next().unwrap_or(PinValue::None);
+                let inner = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(64.0);
+                let outer = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(192.0);
+                let strength = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                wrap_color_field(value, |field| Box::new(VignetteField::new(field, inner, outer, strength)))
+            },
+            NodeType::Scanlines => {
+                let pixmap = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let factor = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.5);
+                let spacing = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(2.0);
+                PinValue::Pixmap(filters::scanlines(&pixmap, factor, spacing.max(1.0) as u32))
+            },
+            NodeType::ChromaticAberration => {
+                let pixmap = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let offset = pins.next().unwrap_or(PinValue::None).vec2().unwrap_or(Vec2::new(2.0, 0.0));
+                PinValue::Pixmap(filters::chromatic_aberration(&pixmap, offset.x, offset.y))
+            },
+            NodeType::Bloom => {
+                let pixmap = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let threshold = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.8);
+                let intensity = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(1.0);
+                PinValue::Pixmap(filters::bloom(&pixmap, threshold, intensity))
+            },
+            NodeType::Mirror(axes) => {
+                let field = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
+                PinValue::ColorField(Box::new(MirrorField::new(field, *axes)))
+            },
+            NodeType::Position => {
+                let (width, height) = settings.size();
+                PinValue::ColorField(Box::new(PositionField::new(width, height)))
+            },
+            NodeType::Rasterize => {
+                let field = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
+                let (width, height) = settings.size();
+                rasterize_tiled(field.as_ref(), width, height, TILE_SIZE).map_or(PinValue::None, PinValue::Pixmap)
+            },
+            NodeType::Spline(_) => {
+                let t = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let points = pins.map(|value| value.vec2().unwrap_or(Vec2::ZERO).to_pos2()).collect();
+                let spline = CatmullRom::new(points);
+                match pin_index {
+                    0 => PinValue::Vec2(spline.eval(t).to_vec2()),
+                    _ => PinValue::Vec2(spline.derivative(t)),
+                }
+            },
+            NodeType::Path(orient, constant_speed) => {
+                let t = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0).clamp(0.0, 1.0);
+                let mut points = pins.map(|value| value.vec2().unwrap_or(Vec2::ZERO).to_pos2());
+                let mut point = || points.next().unwrap_or(Pos2::ZERO);
+                let curve = Bezier::new(point(), point(), point(), point());
+                if pin_index == 1 {
+                    return PinValue::Float(curve.length(ARCLEN_SAMPLES));
+                }
+                let (position, t) = if *constant_speed {
+                    (curve.eval_by_arclen(t), curve.t_at_arclen(t, ARCLEN_SAMPLES))
+                } else {
+                    (curve.eval(t), t)
+                };
+                let angle = if *orient { curve.tangent(t).angle() } else { 0.0 };
+                PinValue::Transform(Transform::from_rotate(angle.to_degrees()).post_translate(position.x, position.y))
+            },
+            NodeType::Comment(_) => PinValue::None,
+            NodeType::ParseColor => {
+                let text = pins.next().unwrap_or(PinValue::None).string().unwrap_or_default();
+                let parsed = Color32::from_hex(text.trim());
+                if parsed.is_err() && !text.is_empty() {
+                    errors.push(format!("\"{}\" is not a hex color", text));
+                }
+                let [r, g, b, a] = parsed.unwrap_or(Color32::TRANSPARENT).to_srgba_unmultiplied();
+                PinValue::Color(Color::from_rgba8(r, g, b, a))
+            },
+            NodeType::SplitRGBA => {
+                let color = pins.next().unwrap_or(PinValue::None).color().unwrap_or(Color::TRANSPARENT);
+                let channels = [color.red(), color.green(), color.blue(), color.alpha()];
+                channels.get(pin_index).map_or(PinValue::None, |channel| PinValue::Float(*channel))
+            },
+            NodeType::CombineRGBA => {
+                let mut channel = |default| pins.next().unwrap_or(PinValue::None).f32().unwrap_or(default).clamp(0.0, 1.0);
+                let (r, g, b, a) = (channel(0.0), channel(0.0), channel(0.0), channel(1.0));
+                Color::from_rgba(r, g, b, a).map_or(PinValue::None, PinValue::Color)
+            },
+            NodeType::Random(seed) => {
+                let salt = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                PinValue::Float(tweening::random(*seed, salt))
+            },
+            NodeType::Step => {
+                let value = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let step = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                // a zero step leaves the value alone
+                PinValue::Float(if step == 0.0 { value } else { (value / step).floor() * step })
+            },
+            NodeType::Abs => PinValue::Float(pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0).abs()),
+            NodeType::Sign => {
+                let value = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                // f32::signum is never zero
+                PinValue::Float(if value == 0.0 { 0.0 } else { value.signum() })
+            },
+            NodeType::Switch => {
+                let selector = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let a = pins.next().unwrap_or(PinValue::None);
+                let b = pins.next().unwrap_or(PinValue::None);
+                if selector < 0.5 { a } else { b }
+            },
+            NodeType::Skew => {
+                let kx = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                let ky = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+                PinValue::Transform(Transform::from_row(1.0, ky, kx, 1.0, 0.0, 0.0))
+            },
+            NodeType::Fit(mode) => {
+                let image = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                PinValue::Transform(mode.transform((image.width(), image.height()), settings.size()))
+            },
+            NodeType::Palette(path) => {
+                // a missing or broken file gives an empty palette, the node shows the error
+                let palette = palette::load(path).unwrap_or_else(|error| {
+                    if !path.as_os_str().is_empty() {
+                        errors.push(error);
+                    }
+                    Vec::new()
+                });
+                PinValue::Palette(palette)
+            },
+            NodeType::Quantize => {
+                let pixmap = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let palette = pins.next().unwrap_or(PinValue::None).palette().unwrap_or_default();
+                PinValue::Pixmap(filters::quantize(&pixmap, &palette))
+            },
+            NodeType::Tile => {
+                let field = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
+                let period = pins.next().unwrap_or(PinValue::None).vec2().unwrap_or(Vec2::splat(32.0));
+                PinValue::ColorField(Box::new(TileField::new(field, period.x, period.y)))
+            },
+            NodeType::Blend(mode) => {
+                let base = pins.next().unwrap_or(PinValue::None);
+                let layer = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
+                wrap_color_field(base, |base| Box::new(BlendField::new(base, layer, *mode)))
+            },
+            NodeType::Background => {
+                // pixmaps keep their size, fields are rasterized at the output size
+                let foreground = match pins.next().unwrap_or(PinValue::None) {
+                    PinValue::Pixmap(pixmap) => Some(pixmap),
+                    value => value.as_color_field().and_then(|field| {
+                        let (width, height) = settings.size();
+                        rasterize_tiled(field.as_ref(), width, height, TILE_SIZE)
+                    }),
+                }.unwrap_or_else(|| settings.empty_pixmap());
+                let color = pins.next().unwrap_or(PinValue::None).color().unwrap_or(Color::BLACK);
+                PinValue::Pixmap(filters::background(&foreground, color))
+            },
+            NodeType::Crop => {
+                let pixmap = pins.next().unwrap_or(PinValue::None).pixmap().unwrap_or_else(|| settings.empty_pixmap());
+                let position = pins.next().unwrap_or(PinValue::None).vec2().unwrap_or(Vec2::ZERO).round();
+                // the rest of the image from the position unless a size is given
+                let size = pins.next().unwrap_or(PinValue::None).vec2()
+                    .unwrap_or(Vec2::new(pixmap.width() as f32, pixmap.height() as f32) - position)
+                    .round();
+                let valid = 1.0..=4096.0;
+                if !valid.contains(&size.x) || !valid.contains(&size.y) {
+                    errors.push(format!("crop size {}x{} is outside 1x1 to 4096x4096", size.x, size.y));
+                    return PinValue::None;
+                }
+                filters::crop(&pixmap, position.x as i32, position.y as i32, size.x as u32, size.y as u32).map_or(PinValue::None, PinValue::Pixmap)
+            },
+            NodeType::Output => {
+                let value = pins.next().unwrap_or(PinValue::None);
+                match value {
+                    PinValue::Pixmap(_) => {},
+                    PinValue::None => errors.push("nothing is connected to the output".into()),
+                    _ => errors.push("output is not an image".into()),
+                }
+                value
+            },
+        }
+    }
+
+    // svg elements and image size for nodes that draw vector shapes, None for nodes that only produce pixels
+    fn svg(&self, pin_values: Vec<PinValue>, settings: &VideoSettings) -> Option<Result<Svg, String>> {
+        match self {
+            NodeType::Hex(orientation, anti_alias) => {
+                let (grid, color, (width, height)) = hex_grid(pin_values.into_iter(), *orientation, *anti_alias, settings);
+                Some(hex_grid_svg(width, height, &grid, color.as_ref()).map(|svg| (svg, (width, height))))
+            },
+            _ => None,
         }
     }
 }
 
+// svg elements and the size of the image they cover
+type Svg = (String, (u32, u32));
+
+// the grid, color field and image size described by the inputs of a hex node, centered on the image
+fn hex_grid(mut pins: impl Iterator<Item = PinValue>, orientation: Orientation, anti_alias: bool, settings: &VideoSettings) -> (HexGrid, Box<dyn Field2<Color>>, (u32, u32)) {
+    let color = pins.next().unwrap_or(PinValue::None).as_color_field().unwrap_or(Box::new(ConstantField::new(Color::TRANSPARENT)));
+    let spacing = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(8.0);
+    let size = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(8.0);
+    let transform = pins.next().unwrap_or(PinValue::None).transform().unwrap_or(Transform::identity());
+    // no outline unless a stroke width is given
+    let stroke = pins.next().unwrap_or(PinValue::None).color().unwrap_or(Color::BLACK);
+    let stroke_width = pins.next().unwrap_or(PinValue::None).f32().unwrap_or(0.0);
+    // the output size unless given, within the limits of the resolution setting
+    let (output_width, output_height) = settings.size();
+    let mut dimension = |default: u32| pins.next().unwrap_or(PinValue::None).f32().filter(|value| value.is_finite()).map_or(default, |value| value.round().clamp(1.0, 4096.0) as u32);
+    let (width, height) = (dimension(output_width), dimension(output_height));
+    let grid = HexGrid::new(spacing, size, transform.post_translate(0.5 * width as f32, 0.5 * height as f32), orientation)
+        .with_anti_alias(anti_alias && settings.anti_alias)
+        .with_stroke(stroke, stroke_width);
+    (grid, color, (width, height))
+}
+
 impl NodeWidget for NodeType {
     fn in_pins(&self) -> Vec<Pin> {
         match self {
-            NodeType::Lerp => [Pin::new(), Pin::new(), Pin::new()].into(),
-            NodeType::Cubic(_) => [Pin::new()].into(),
-            NodeType::Revolution => [Pin::new()].into(),
-            NodeType::Rotate => [Pin::new()].into(),
-            NodeType::Scale => [Pin::new(), Pin::new()].into(),
-            NodeType::TransformColorField => [Pin::new(), Pin::new()].into(),
-            NodeType::Hex => [Pin::new(), Pin::new(), Pin::new(), Pin::new()].into(),
-            NodeType::Output => [Pin::new()].into(),
+            NodeType::Lerp => [Pin::named("a", PinType::Float), Pin::named("b", PinType::Float), Pin::named("t", PinType::Float)].into(),
+            NodeType::Cubic(_) => [Pin::named("t", PinType::Float)].into(),
+            NodeType::Revolution => [Pin::named("turns", PinType::Float)].into(),
+            NodeType::Rotate => [Pin::named("angle", PinType::Float)].into(),
+            NodeType::Scale => [Pin::named("sx", PinType::Float), Pin::named("sy", PinType::Float)].into(),
+            NodeType::TransformColorField => [Pin::named("field", PinType::ColorField), Pin::named("transform", PinType::Transform)].into(),
+            NodeType::Hex(..) => [
+                Pin::named("color", PinType::ColorField),
+                Pin::named("spacing", PinType::Float),
+                Pin::named("size", PinType::Float),
+                Pin::named("transform", PinType::Transform),
+                Pin::named("stroke", PinType::Color),
+                Pin::named("stroke width", PinType::Float),
+                Pin::named("width", PinType::Float),
+                Pin::named("height", PinType::Float),
+            ].into(),
+            NodeType::Wave(_) => [Pin::named("phase", PinType::Float), Pin::named("frequency", PinType::Float), Pin::named("amplitude", PinType::Float)].into(),
+            NodeType::PingPong => [Pin::named("t", PinType::Float)].into(),
+            NodeType::Offset(_) => [Pin::named("value", PinType::Float), Pin::named("delay", PinType::Float)].into(),
+            NodeType::Remap => [
+                Pin::named("value", PinType::Float),
+                Pin::named("in min", PinType::Float),
+                Pin::named("in max", PinType::Float),
+                Pin::named("out min", PinType::Float),
+                Pin::named("out max", PinType::Float),
+            ].into(),
+            NodeType::Smoothstep => [Pin::named("edge0", PinType::Float), Pin::named("edge1", PinType::Float), Pin::named("x", PinType::Float)].into(),
+            NodeType::Pixelate => [Pin::named("pixmap", PinType::Pixmap), Pin::named("block", PinType::Float)].into(),
+            NodeType::EdgeDetect => [Pin::named("pixmap", PinType::Pixmap), Pin::named("threshold", PinType::Float)].into(),
+            NodeType::Polar => [Pin::named("field", PinType::ColorField), Pin::named("repeat", PinType::Float), Pin::named("twist", PinType::Float)].into(),
+            NodeType::Displace(_) => [
+                Pin::named("source", PinType::Pixmap),
+                Pin::named("offset", PinType::Pixmap),
+                Pin::named("strength", PinType::Float),
+            ].into(),
+            NodeType::Gamma => [Pin::named("field", PinType::ColorField), Pin::named("gamma", PinType::Float)].into(),
+            NodeType::Opacity => [Pin::named("field", PinType::ColorField), Pin::named("opacity", PinType::Float)].into(),
+            NodeType::Grayscale => [Pin::named("field", PinType::ColorField)].into(),
+            NodeType::Vignette => [
+                Pin::named("field", PinType::ColorField),
+                Pin::named("inner", PinType::Float),
+                Pin::named("outer", PinType::Float),
+                Pin::named("strength", PinType::Float),
+            ].into(),
+            NodeType::Scanlines => [
+                Pin::named("pixmap", PinType::Pixmap),
+                Pin::named("factor", PinType::Float),
+                Pin::named("spacing", PinType::Float),
+            ].into(),
+            NodeType::ChromaticAberration => [Pin::named("pixmap", PinType::Pixmap), Pin::named("offset", PinType::Vec2)].into(),
+            NodeType::Bloom => [
+                Pin::named("pixmap", PinType::Pixmap),
+                Pin::named("threshold", PinType::Float),
+                Pin::named("intensity", PinType::Float),
+            ].into(),
+            NodeType::Mirror(_) => [Pin::named("field", PinType::ColorField)].into(),
+            NodeType::Rasterize => [Pin::named("field", PinType::ColorField)].into(),
+            NodeType::Spline(count) => std::iter::once(Pin::named("t", PinType::Float))
+                .chain((0..*count).map(|index| Pin::named(&format!("p{}", index), PinType::Vec2)))
+                .collect(),
+            NodeType::Path(..) => [
+                Pin::named("t", PinType::Float),
+                Pin::named("a", PinType::Vec2),
+                Pin::named("b", PinType::Vec2),
+                Pin::named("c", PinType::Vec2),
+                Pin::named("d", PinType::Vec2),
+            ].into(),
+            NodeType::ParseColor => [Pin::named("hex", PinType::String)].into(),
+            NodeType::SplitRGBA => [Pin::named("color", PinType::Color)].into(),
+            NodeType::CombineRGBA => [
+                Pin::named("r", PinType::Float),
+                Pin::named("g", PinType::Float),
+                Pin::named("b", PinType::Float),
+                Pin::named("a", PinType::Float),
+            ].into(),
+            NodeType::Random(_) => [Pin::named("salt", PinType::Float)].into(),
+            NodeType::Step => [Pin::named("value", PinType::Float), Pin::named("step", PinType::Float)].into(),
+            NodeType::Abs => [Pin::named("value", PinType::Float)].into(),
+            NodeType::Sign => [Pin::named("value", PinType::Float)].into(),
+            NodeType::Switch => [
+                Pin::named("select", PinType::Float),
+                Pin::named("a", PinType::Any),
+                Pin::named("b", PinType::Any),
+            ].into(),
+            NodeType::Skew => [Pin::named("kx", PinType::Float), Pin::named("ky", PinType::Float)].into(),
+            NodeType::Fit(_) => [Pin::named("image", PinType::Pixmap)].into(),
+            NodeType::Quantize => [Pin::named("pixmap", PinType::Pixmap), Pin::named("palette", PinType::Palette)].into(),
+            NodeType::Tile => [Pin::named("field", PinType::ColorField), Pin::named("period", PinType::Vec2)].into(),
+            NodeType::Blend(_) => [Pin::named("base", PinType::ColorField), Pin::named("layer", PinType::ColorField)].into(),
+            NodeType::Background => [Pin::named("foreground", PinType::ColorField), Pin::named("color", PinType::Color)].into(),
+            NodeType::Crop => [Pin::named("pixmap", PinType::Pixmap), Pin::named("position", PinType::Vec2), Pin::named("size", PinType::Vec2)].into(),
+            NodeType::Output => [Pin::new(PinType::Any)].into(),
             _ => Vec::new(),
         }
     }
     fn out_pins(&self) -> Vec<Pin> {
         match self {
-            NodeType::Time => [Pin::new()].into(),
-            NodeType::Float(_) => [Pin::new()].into(),
-            NodeType::String(_) => [Pin::new()].into(),
-            NodeType::Color(_) => [Pin::new()].into(),
-            NodeType::Lerp => [Pin::new()].into(),
-            NodeType::Cubic(_) => [Pin::new()].into(),
-            NodeType::Pixmap(_) => [Pin::new()].into(),
-            NodeType::TransformColorField => [Pin::new()].into(),
-            NodeType::Revolution => [Pin::new()].into(),
-            NodeType::Rotate => [Pin::new()].into(),
-            NodeType::Scale => [Pin::new()].into(),
-            NodeType::Hex => [Pin::new()].into(),
+            NodeType::Time => [Pin::named("t", PinType::Float)].into(),
+            NodeType::Float(_) => [Pin::new(PinType::Float)].into(),
+            NodeType::Vec2(_) => [Pin::new(PinType::Vec2)].into(),
+            NodeType::String(_) => [Pin::new(PinType::String)].into(),
+            NodeType::Color(_) => [Pin::new(PinType::Color)].into(),
+            NodeType::Lerp => [Pin::new(PinType::Float)].into(),
+            NodeType::Cubic(_) => [Pin::new(PinType::Float)].into(),
+            NodeType::Pixmap(..) => [Pin::named("pixmap", PinType::Pixmap), Pin::named("field", PinType::ColorField)].into(),
+            NodeType::TransformColorField => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Revolution => [Pin::named("angle", PinType::Float)].into(),
+            NodeType::Rotate => [Pin::new(PinType::Transform)].into(),
+            NodeType::Scale => [Pin::new(PinType::Transform)].into(),
+            NodeType::Hex(..) => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Wave(_) => [Pin::new(PinType::Float)].into(),
+            NodeType::PingPong => [Pin::new(PinType::Float)].into(),
+            NodeType::Offset(_) => [Pin::new(PinType::Float)].into(),
+            NodeType::Remap => [Pin::new(PinType::Float)].into(),
+            NodeType::Smoothstep => [Pin::new(PinType::Float)].into(),
+            NodeType::Pixelate => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::EdgeDetect => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Polar => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Displace(_) => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Gamma => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Opacity => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Grayscale => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Vignette => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Scanlines => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::ChromaticAberration => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Bloom => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Mirror(_) => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Position => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Rasterize => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Spline(_) => [Pin::named("position", PinType::Vec2), Pin::named("velocity", PinType::Vec2)].into(),
+            NodeType::Path(..) => [Pin::named("transform", PinType::Transform), Pin::named("length", PinType::Float)].into(),
+            NodeType::Comment(_) => Vec::new(),
+            NodeType::ParseColor => [Pin::new(PinType::Color)].into(),
+            NodeType::SplitRGBA => [
+                Pin::named("r", PinType::Float),
+                Pin::named("g", PinType::Float),
+                Pin::named("b", PinType::Float),
+                Pin::named("a", PinType::Float),
+            ].into(),
+            NodeType::CombineRGBA => [Pin::new(PinType::Color)].into(),
+            NodeType::Random(_) => [Pin::new(PinType::Float)].into(),
+            NodeType::Step => [Pin::new(PinType::Float)].into(),
+            NodeType::Abs => [Pin::new(PinType::Float)].into(),
+            NodeType::Sign => [Pin::new(PinType::Float)].into(),
+            NodeType::Switch => [Pin::new(PinType::Any)].into(),
+            NodeType::Skew => [Pin::new(PinType::Transform)].into(),
+            NodeType::Fit(_) => [Pin::new(PinType::Transform)].into(),
+            NodeType::Palette(_) => [Pin::new(PinType::Palette)].into(),
+            NodeType::Quantize => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Tile => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Blend(_) => [Pin::new(PinType::ColorField)].into(),
+            NodeType::Background => [Pin::new(PinType::Pixmap)].into(),
+            NodeType::Crop => [Pin::new(PinType::Pixmap)].into(),
             NodeType::Output => Vec::new(),
         }
     }
@@ -190,91 +697,414 @@ impl NodeWidget for NodeType {
         match self {
             NodeType::Time => "time",
             NodeType::Float(_) => "float",
+            NodeType::Vec2(_) => "vec2",
             NodeType::String(_) => "text",
             NodeType::Color(_) => "color",
             NodeType::Lerp => "lerp",
             NodeType::Cubic(_) => "cubic",
-            NodeType::Pixmap(_) => "pixmap",
+            NodeType::Pixmap(..) => "pixmap",
             NodeType::TransformColorField => "transform color field",
             NodeType::Revolution => "revolution",
             NodeType::Rotate => "rotate",
             NodeType::Scale => "scale",
-            NodeType::Hex => "hex",
+            NodeType::Hex(..) => "hex",
+            NodeType::Wave(_) => "wave",
+            NodeType::PingPong => "ping-pong",
+            NodeType::Offset(_) => "offset",
+            NodeType::Remap => "remap",
+            NodeType::Smoothstep => "smoothstep",
+            NodeType::Pixelate => "pixelate",
+            NodeType::EdgeDetect => "edge detect",
+            NodeType::Polar => "polar",
+            NodeType::Displace(_) => "displace",
+            NodeType::Gamma => "gamma",
+            NodeType::Opacity => "opacity",
+            NodeType::Grayscale => "grayscale",
+            NodeType::Vignette => "vignette",
+            NodeType::Scanlines => "scanlines",
+            NodeType::ChromaticAberration => "chromatic aberration",
+            NodeType::Bloom => "bloom",
+            NodeType::Mirror(_) => "mirror",
+            NodeType::Position => "position",
+            NodeType::Rasterize => "rasterize",
+            NodeType::Spline(_) => "spline",
+            NodeType::Path(..) => "path",
+            NodeType::Comment(_) => "comment",
+            NodeType::ParseColor => "parse color",
+            NodeType::SplitRGBA => "split rgba",
+            NodeType::CombineRGBA => "combine rgba",
+            NodeType::Random(_) => "random",
+            NodeType::Step => "step",
+            NodeType::Abs => "abs",
+            NodeType::Sign => "sign",
+            NodeType::Switch => "switch",
+            NodeType::Skew => "skew",
+            NodeType::Fit(_) => "fit",
+            NodeType::Palette(_) => "palette",
+            NodeType::Quantize => "quantize",
+            NodeType::Tile => "tile",
+            NodeType::Blend(_) => "blend",
+            NodeType::Background => "background",
+            NodeType::Crop => "crop",
             NodeType::Output => "output",
         }.into()
     }
     fn ui(&mut self, ui: &mut egui::Ui) -> egui::Response {
         match self {
             NodeType::Float(value) => ui.add(egui::Slider::new(value, 0.0..=256.0).logarithmic(true)),
+            NodeType::Vec2(value) => {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut value.x).prefix("x "));
+                    ui.add(egui::DragValue::new(&mut value.y).prefix("y "));
+                }).response
+            },
             NodeType::Color(value) => {
-                egui::color_picker::color_picker_color32(ui, value, egui::color_picker::Alpha::Opaque);
+                egui::color_picker::color_picker_color32(ui, value, egui::color_picker::Alpha::OnlyBlend);
                 ui.response()
             },
-            NodeType::Pixmap(path) => {
+            NodeType::Pixmap(path, sampling, address_mode) => {
+                let mut text = path.to_str().unwrap_or("").to_string();
+                let response = ui.text_edit_singleline(&mut text);
+                *path = text.into();
+                if !path.as_os_str().is_empty() {
+                    if let Some(error) = images::error(path) {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                }
+                ui.horizontal(|ui| {
+                    for mode in Sampling::ALL {
+                        ui.selectable_value(sampling, mode, mode.name());
+                    }
+                });
+                egui::ComboBox::from_id_salt("address")
+                    .selected_text(address_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in AddressMode::ALL {
+                            ui.selectable_value(address_mode, mode, mode.name());
+                        }
+                    });
+                response
+            },
+            NodeType::Hex(orientation, anti_alias) => {
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        for value in Orientation::ALL {
+                            ui.selectable_value(orientation, value, value.name());
+                        }
+                    });
+                    ui.checkbox(anti_alias, "anti-alias");
+                }).response
+            },
+            NodeType::Wave(waveform) => {
+                egui::ComboBox::from_id_salt("waveform")
+                    .selected_text(waveform.name())
+                    .show_ui(ui, |ui| {
+                        for value in Waveform::ALL {
+                            ui.selectable_value(waveform, value, value.name());
+                        }
+                    }).response
+            },
+            NodeType::Offset(wrap) => ui.checkbox(wrap, "wrap"),
+            NodeType::Displace(address_mode) => {
+                egui::ComboBox::from_id_salt("address")
+                    .selected_text(address_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in AddressMode::ALL {
+                            ui.selectable_value(address_mode, mode, mode.name());
+                        }
+                    }).response
+            },
+            NodeType::Mirror(axes) => {
+                ui.horizontal(|ui| {
+                    for value in MirrorAxes::ALL {
+                        ui.selectable_value(axes, value, value.name());
+                    }
+                }).response
+            },
+            NodeType::Spline(count) => ui.add(egui::DragValue::new(count).range(2..=8).prefix("points ")),
+            NodeType::Path(orient, constant_speed) => ui.vertical(|ui| {
+                ui.checkbox(orient, "orient to path");
+                ui.checkbox(constant_speed, "constant speed");
+            }).response,
+            NodeType::Comment(text) => ui.add(egui::TextEdit::multiline(text).desired_width(240.0).desired_rows(8)),
+            NodeType::Random(seed) => ui.add(egui::DragValue::new(seed).prefix("seed ")),
+            NodeType::Fit(mode) => {
+                ui.horizontal(|ui| {
+                    for value in FitMode::ALL {
+                        ui.selectable_value(mode, value, value.name());
+                    }
+                }).response
+            },
+            NodeType::Palette(path) => {
                 let mut text = path.to_str().unwrap_or("").to_string();
                 let response = ui.text_edit_singleline(&mut text);
                 *path = text.into();
+                if !path.as_os_str().is_empty() {
+                    match palette::count(path) {
+                        Ok(count) => { ui.weak(format!("{} colors", count)); },
+                        Err(error) => { ui.colored_label(ui.visuals().error_fg_color, error); },
+                    }
+                }
                 response
             },
+            NodeType::Blend(mode) => {
+                egui::ComboBox::from_id_salt("blend")
+                    .selected_text(mode.name())
+                    .show_ui(ui, |ui| {
+                        for value in BlendMode::ALL {
+                            ui.selectable_value(mode, value, value.name());
+                        }
+                    }).response
+            },
             _ => ui.response(),
         }
     }
+    fn in_background(&self) -> bool {
+        matches!(self, NodeType::Comment(_))
+    }
+    // every graph needs an output
+    fn removable(&self) -> bool {
+        !matches!(self, NodeType::Output)
+    }
+}
+
+type NodeConstructor = fn() -> NodeType;
+
+// every node type that can be created from the context menu as (category, title, constructor)
+const NODE_REGISTRY: &[(&str, &str, NodeConstructor)] = &[
+    ("data", "float", || NodeType::Float(1.0)),
+    ("data", "vec2", || NodeType::Vec2(Vec2::ZERO)),
+    ("data", "text", || NodeType::String(String::new())),
+    ("data", "color", || NodeType::Color(Color32::GRAY)),
+    ("data", "time", || NodeType::Time),
+    ("data", "parse color", || NodeType::ParseColor),
+    ("data", "split rgba", || NodeType::SplitRGBA),
+    ("data", "combine rgba", || NodeType::CombineRGBA),
+    ("data", "random", || NodeType::Random(0)),
+    ("data", "switch", || NodeType::Switch),
+    ("data", "palette", || NodeType::Palette(PathBuf::new())),
+    ("tween", "lerp", || NodeType::Lerp),
+    ("tween", "cubic", || NodeType::Cubic(true)),
+    ("tween", "wave", || NodeType::Wave(Waveform::Sine)),
+    ("tween", "ping-pong", || NodeType::PingPong),
+    ("tween", "offset", || NodeType::Offset(true)),
+    ("tween", "remap", || NodeType::Remap),
+    ("tween", "smoothstep", || NodeType::Smoothstep),
+    ("tween", "spline", || NodeType::Spline(4)),
+    ("tween", "step", || NodeType::Step),
+    ("tween", "abs", || NodeType::Abs),
+    ("tween", "sign", || NodeType::Sign),
+    ("field", "pixmap", || NodeType::Pixmap(PathBuf::new(), Sampling::Nearest, AddressMode::Transparent)),
+    ("field", "transform color field", || NodeType::TransformColorField),
+    ("field", "polar", || NodeType::Polar),
+    ("field", "mirror", || NodeType::Mirror(MirrorAxes::Horizontal)),
+    ("field", "position", || NodeType::Position),
+    ("field", "rasterize", || NodeType::Rasterize),
+    ("field", "tile", || NodeType::Tile),
+    ("field", "blend", || NodeType::Blend(BlendMode::Multiply)),
+    ("transform", "rotate", || NodeType::Rotate),
+    ("transform", "scale", || NodeType::Scale),
+    ("transform", "revolution", || NodeType::Revolution),
+    ("transform", "path", || NodeType::Path(true, false)),
+    ("transform", "skew", || NodeType::Skew),
+    ("transform", "fit", || NodeType::Fit(FitMode::Contain)),
+    ("draw", "hex", || NodeType::Hex(Orientation::PointyTop, true)),
+    ("filter", "pixelate", || NodeType::Pixelate),
+    ("filter", "edge detect", || NodeType::EdgeDetect),
+    ("filter", "displace", || NodeType::Displace(AddressMode::Clamp)),
+    ("filter", "gamma", || NodeType::Gamma),
+    ("filter", "opacity", || NodeType::Opacity),
+    ("filter", "grayscale", || NodeType::Grayscale),
+    ("filter", "vignette", || NodeType::Vignette),
+    ("filter", "scanlines", || NodeType::Scanlines),
+    ("filter", "chromatic aberration", || NodeType::ChromaticAberration),
+    ("filter", "bloom", || NodeType::Bloom),
+    ("filter", "quantize", || NodeType::Quantize),
+    ("filter", "background", || NodeType::Background),
+    ("filter", "crop", || NodeType::Crop),
+    ("graph", "comment", || NodeType::Comment(String::new())),
+    ("output", "output", || NodeType::Output),
+];
+
+// true if all characters of the pattern appear in order in the text, ignoring case
+fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern.chars().flat_map(char::to_lowercase).all(|p| text.any(|c| c == p))
 }
 
-fn into_node(raw: &json::JsonValue) -> Option<NodeType> {
-    let node_type_raw = raw["type"].as_str().unwrap();
-    match node_type_raw {
+fn into_node(raw: &json::JsonValue) -> Result<NodeType, json::Error> {
+    let node_type_raw = raw["type"].as_str().ok_or_else(|| json::Error::WrongType("node without a type".into()))?;
+    let node = match node_type_raw {
         "time" => Some(NodeType::Time),
-        "float" => raw["value"].as_f32().map(|value| NodeType::Float(value)),
+        "float" => raw["value"].as_f32().map(NodeType::Float),
+        "vec2" => raw["x"].as_f32().zip(raw["y"].as_f32()).map(|(x, y)| NodeType::Vec2(Vec2::new(x, y))),
         "string" => raw["value"].as_str().map(|value| NodeType::String(value.to_string())),
-        "color" => raw["value"].as_str().map(|value| Color32::from_hex(value).ok().map(|value| NodeType::Color(value)))?,
+        "color" => raw["value"].as_str().and_then(|value| Color32::from_hex(value).ok()).map(NodeType::Color),
         "lerp" => Some(NodeType::Lerp),
-        "cubic" =>  raw["in"].as_bool().map(|value| NodeType::Cubic(value.into())),
-        "pixmap" => raw["path"].as_str().map(|value| NodeType::Pixmap(value.into())),
+        "cubic" => raw["in"].as_bool().map(NodeType::Cubic),
+        "pixmap" => {
+            let sampling = Sampling::ALL.into_iter().find(|mode| raw["sampling"] == mode.name()).unwrap_or(Sampling::Nearest);
+            let address_mode = AddressMode::ALL.into_iter().find(|mode| raw["address"] == mode.name()).unwrap_or(AddressMode::Transparent);
+            raw["path"].as_str().map(|value| NodeType::Pixmap(value.into(), sampling, address_mode))
+        },
         "transform-color-field" => Some(NodeType::TransformColorField),
         "revolution" => Some(NodeType::Revolution),
         "rotate" => Some(NodeType::Rotate),
         "scale" => Some(NodeType::Scale),
-        "hex" => Some(NodeType::Hex),
+        "hex" => {
+            let orientation = Orientation::ALL.into_iter().find(|value| raw["orientation"] == value.name());
+            Some(NodeType::Hex(orientation.unwrap_or(Orientation::PointyTop), raw["anti_alias"].as_bool().unwrap_or(true)))
+        },
+        "wave" => {
+            let waveform = Waveform::ALL.into_iter().find(|value| raw["shape"] == value.name());
+            Some(NodeType::Wave(waveform.unwrap_or(Waveform::Sine)))
+        },
+        "ping-pong" => Some(NodeType::PingPong),
+        "offset" => Some(NodeType::Offset(raw["wrap"].as_bool().unwrap_or(false))),
+        "remap" => Some(NodeType::Remap),
+        "smoothstep" => Some(NodeType::Smoothstep),
+        "pixelate" => Some(NodeType::Pixelate),
+        "edge-detect" => Some(NodeType::EdgeDetect),
+        "polar" => Some(NodeType::Polar),
+        "displace" => {
+            let address_mode = AddressMode::ALL.into_iter().find(|mode| raw["address"] == mode.name());
+            Some(NodeType::Displace(address_mode.unwrap_or(AddressMode::Clamp)))
+        },
+        "gamma" => Some(NodeType::Gamma),
+        "opacity" => Some(NodeType::Opacity),
+        "grayscale" => Some(NodeType::Grayscale),
+        "vignette" => Some(NodeType::Vignette),
+        "scanlines" => Some(NodeType::Scanlines),
+        "chromatic-aberration" => Some(NodeType::ChromaticAberration),
+        "bloom" => Some(NodeType::Bloom),
+        "mirror" => {
+            let axes = MirrorAxes::ALL.into_iter().find(|value| raw["axes"] == value.name());
+            Some(NodeType::Mirror(axes.unwrap_or(MirrorAxes::Horizontal)))
+        },
+        "position" => Some(NodeType::Position),
+        "rasterize" => Some(NodeType::Rasterize),
+        "spline" => Some(NodeType::Spline(raw["points"].as_usize().unwrap_or(4).clamp(2, 8))),
+        "path" => Some(NodeType::Path(raw["orient"].as_bool().unwrap_or(true), raw["constant_speed"].as_bool().unwrap_or(false))),
+        "comment" => Some(NodeType::Comment(raw["text"].as_str().unwrap_or_default().into())),
+        "parse-color" => Some(NodeType::ParseColor),
+        "split-rgba" => Some(NodeType::SplitRGBA),
+        "combine-rgba" => Some(NodeType::CombineRGBA),
+        "random" => Some(NodeType::Random(raw["seed"].as_u32().unwrap_or(0))),
+        "step" => Some(NodeType::Step),
+        "abs" => Some(NodeType::Abs),
+        "sign" => Some(NodeType::Sign),
+        "switch" => Some(NodeType::Switch),
+        "skew" => Some(NodeType::Skew),
+        "fit" => {
+            let mode = FitMode::ALL.into_iter().find(|value| raw["mode"] == value.name());
+            Some(NodeType::Fit(mode.unwrap_or(FitMode::Contain)))
+        },
+        "palette" => raw["path"].as_str().map(|value| NodeType::Palette(value.into())),
+        "quantize" => Some(NodeType::Quantize),
+        "tile" => Some(NodeType::Tile),
+        "blend" => {
+            let mode = BlendMode::ALL.into_iter().find(|value| raw["mode"] == value.name());
+            Some(NodeType::Blend(mode.unwrap_or(BlendMode::Multiply)))
+        },
+        "background" => Some(NodeType::Background),
+        "crop" => Some(NodeType::Crop),
         "output" => Some(NodeType::Output),
-        _ => None
-    }
+        _ => return Err(json::Error::WrongType(format!("unknown node type \"{}\"", node_type_raw))),
+    };
+    node.ok_or_else(|| json::Error::WrongType(format!("bad value for {} node", node_type_raw)))
 }
 
-fn into_pinid(raw: &json::JsonValue, direction: PinDirection) -> PinId {
-    PinId {
-        node_index: raw["node"].as_usize().unwrap(),
-        pin_index: raw["pin"].as_usize().unwrap(),
+fn into_pinid(raw: &json::JsonValue, direction: PinDirection) -> Option<PinId> {
+    Some(PinId {
+        node_index: raw["node"].as_usize()?,
+        pin_index: raw["pin"].as_usize()?,
         direction,
-    }
+    })
 }
 fn into_link(raw: &json::JsonValue) -> Option<(PinId, PinId)> {
-    Some((into_pinid(&raw["from"], PinDirection::Output), into_pinid(&raw["to"], PinDirection::Input)))
+    Some((into_pinid(&raw["from"], PinDirection::Output)?, into_pinid(&raw["to"], PinDirection::Input)?))
 }
 
 // graph io
 fn load_graph(root: &json::JsonValue) -> Result<Graph<NodeType>, json::Error> {
-    let nodes: Vec<NodeType> = root["nodes"].members().filter_map(|raw| into_node(&raw)).collect();
-    let mut links: Vec<(PinId, PinId)> = root["links"].members().filter_map(|raw| into_link(raw)).collect();
-    // drop bad links
-    links.retain(|(from, to)| from.node_index < nodes.len() && to.node_index < nodes.len());
-    Ok(Graph { nodes, links })
+    let pan = egui::Vec2::new(root["view"]["x"].as_f32().unwrap_or(0.0), root["view"]["y"].as_f32().unwrap_or(0.0));
+    let zoom = root["view"]["zoom"].as_f32().unwrap_or(1.0);
+    let mut graph = Graph::with_view(Vec::new(), Vec::new(), pan, zoom);
+    // a missing node would shift the indices of all links after it, so fail instead of skipping
+    for raw in root["nodes"].members() {
+        let widget = into_node(raw)?;
+        // older files have no positions and get cascaded
+        let position = raw["position"]["x"].as_f32().zip(raw["position"]["y"].as_f32());
+        let position = position.map_or_else(|| graph.next_position(), |(x, y)| Pos2::new(x, y));
+        let mut node = Node::new(widget, position);
+        node.collapsed = raw["collapsed"].as_bool().unwrap_or(false);
+        node.muted = raw["muted"].as_bool().unwrap_or(false);
+        graph.nodes.push(node);
+    }
+    graph.links = root["links"].members().filter_map(into_link).collect();
+    // drop links to nodes or pins that don't exist
+    let nodes = &graph.nodes;
+    graph.links.retain(|(from, to)|
+        nodes.get(from.node_index).is_some_and(|node| from.pin_index < node.widget.out_pins().len()) &&
+        nodes.get(to.node_index).is_some_and(|node| to.pin_index < node.widget.in_pins().len()));
+    Ok(graph)
 }
 
 fn from_nodetype(node_type: NodeType) -> json::JsonValue {
     match node_type {
         NodeType::Time => json::object!{"type": "time"},
         NodeType::Float(value) => json::object!{"type": "float", value: value},
+        NodeType::Vec2(value) => json::object!{"type": "vec2", x: value.x, y: value.y},
         NodeType::String(value) => json::object!{"type": "string", value: value},
         NodeType::Color(value) => json::object!{"type": "color", value: value.to_hex()},
         NodeType::Lerp => json::object!{"type": "lerp"},
         NodeType::Cubic(is_in) => json::object!{"type": "cubic", "in": is_in},
-        NodeType::Pixmap(path) => json::object!{"type": "pixmap", path: path.to_str()},
+        NodeType::Pixmap(path, sampling, address_mode) => json::object!{
+            "type": "pixmap", path: path.to_str(), sampling: sampling.name(), address: address_mode.name()
+        },
         NodeType::TransformColorField => json::object!{"type": "transform-color-field" },
         NodeType::Revolution => json::object!{"type": "revolution"},
         NodeType::Rotate => json::object!{"type": "rotate"},
         NodeType::Scale => json::object!{"type": "scale"},
-        NodeType::Hex => json::object!{"type": "hex"},
+        NodeType::Hex(orientation, anti_alias) => json::object!{"type": "hex", orientation: orientation.name(), anti_alias: anti_alias},
+        NodeType::Wave(waveform) => json::object!{"type": "wave", shape: waveform.name()},
+        NodeType::PingPong => json::object!{"type": "ping-pong"},
+        NodeType::Offset(wrap) => json::object!{"type": "offset", wrap: wrap},
+        NodeType::Remap => json::object!{"type": "remap"},
+        NodeType::Smoothstep => json::object!{"type": "smoothstep"},
+        NodeType::Pixelate => json::object!{"type": "pixelate"},
+        NodeType::EdgeDetect => json::object!{"type": "edge-detect"},
+        NodeType::Polar => json::object!{"type": "polar"},
+        NodeType::Displace(address_mode) => json::object!{"type": "displace", address: address_mode.name()},
+        NodeType::Gamma => json::object!{"type": "gamma"},
+        NodeType::Opacity => json::object!{"type": "opacity"},
+        NodeType::Grayscale => json::object!{"type": "grayscale"},
+        NodeType::Vignette => json::object!{"type": "vignette"},
+        NodeType::Scanlines => json::object!{"type": "scanlines"},
+        NodeType::ChromaticAberration => json::object!{"type": "chromatic-aberration"},
+        NodeType::Bloom => json::object!{"type": "bloom"},
+        NodeType::Mirror(axes) => json::object!{"type": "mirror", axes: axes.name()},
+        NodeType::Position => json::object!{"type": "position"},
+        NodeType::Rasterize => json::object!{"type": "rasterize"},
+        NodeType::Spline(count) => json::object!{"type": "spline", points: count},
+        NodeType::Path(orient, constant_speed) => json::object!{"type": "path", orient: orient, constant_speed: constant_speed},
+        NodeType::Comment(text) => json::object!{"type": "comment", text: text},
+        NodeType::ParseColor => json::object!{"type": "parse-color"},
+        NodeType::SplitRGBA => json::object!{"type": "split-rgba"},
+        NodeType::CombineRGBA => json::object!{"type": "combine-rgba"},
+        NodeType::Random(seed) => json::object!{"type": "random", seed: seed},
+        NodeType::Step => json::object!{"type": "step"},
+        NodeType::Abs => json::object!{"type": "abs"},
+        NodeType::Sign => json::object!{"type": "sign"},
+        NodeType::Switch => json::object!{"type": "switch"},
+        NodeType::Skew => json::object!{"type": "skew"},
+        NodeType::Fit(mode) => json::object!{"type": "fit", mode: mode.name()},
+        NodeType::Palette(path) => json::object!{"type": "palette", path: path.to_str()},
+        NodeType::Quantize => json::object!{"type": "quantize"},
+        NodeType::Tile => json::object!{"type": "tile"},
+        NodeType::Blend(mode) => json::object!{"type": "blend", mode: mode.name()},
+        NodeType::Background => json::object!{"type": "background"},
+        NodeType::Crop => json::object!{"type": "crop"},
         NodeType::Output => json::object!{"type": "output"},
     }
 }
@@ -283,7 +1113,11 @@ fn save_graph(graph: &Graph<NodeType>) -> Result<json::JsonValue, json::JsonErro
     let mut root = json::JsonValue::new_object();
     root["nodes"] = JsonValue::new_array();
     for node in &graph.nodes {
-        root["nodes"].push(from_nodetype(node.clone()))?;
+        let mut raw = from_nodetype(node.widget.clone());
+        raw["position"] = json::object!{x: node.position.x, y: node.position.y};
+        raw["collapsed"] = node.collapsed.into();
+        raw["muted"] = node.muted.into();
+        root["nodes"].push(raw)?;
     }
 
     root["links"] = JsonValue::new_array();
@@ -295,41 +1129,240 @@ fn save_graph(graph: &Graph<NodeType>) -> Result<json::JsonValue, json::JsonErro
             }
         )?;
     }
+    root["view"] = json::object!{x: graph.pan.x, y: graph.pan.y, zoom: graph.zoom};
     Ok(root)
 }
 
-fn save_timeline(timeline: &Timeline<Graph<NodeType>>) -> Result<json::JsonValue, json::JsonError> {
-    let mut root = json::JsonValue::new_array();
-    for (duration, graph) in &timeline.blocks {
-        let graph_json = save_graph(graph)?;
-        root.push(json::object!{
-            duration: duration.as_millis(),
+// graphviz digraph with an edge per link, labeled output pin:input pin
+fn graph_to_dot(graph: &Graph<NodeType>) -> String {
+    let mut dot = String::from("digraph {\n");
+    for (index, node) in graph.nodes.iter().enumerate() {
+        dot += &format!("    n{} [label=\"{}\"];\n", index, node.widget.title().replace('"', "\\\""));
+    }
+    for (from, to) in &graph.links {
+        dot += &format!("    n{} -> n{} [label=\"{}:{}\"];\n", from.node_index, to.node_index, from.pin_index, to.pin_index);
+    }
+    dot += "}\n";
+    dot
+}
+
+// a timeline is saved as its video settings and its array of blocks
+fn save_timeline(timeline: &Timeline<Graph<NodeType>>, settings: &VideoSettings) -> Result<json::JsonValue, json::JsonError> {
+    let mut blocks = json::JsonValue::new_array();
+    for block in &timeline.blocks {
+        let graph_json = save_graph(&block.content)?;
+        blocks.push(json::object!{
+            duration: block.duration.as_millis(),
+            transition: block.transition.as_millis(),
             graph: graph_json,
         })?;
     }
-    Ok(root)
+    Ok(json::object!{settings: save_settings(settings, timeline.fps), blocks: blocks})
 }
 
-fn load_timeline(raw: &str) -> Result<Timeline<Graph<NodeType>>, json::Error> {
+// older timelines are just the array of blocks and get the default settings
+fn load_timeline(raw: &str) -> Result<(Timeline<Graph<NodeType>>, VideoSettings), json::Error> {
     let root = json::parse(raw)?;
-    let mut timeline = Timeline::new(30.0);
-    for block in root.members() {
-        let duration = Duration::from_millis(block["duration"].as_u32().unwrap_or(3000));
+    let (blocks, settings) = if root.is_array() { (&root, &JsonValue::Null) } else { (&root["blocks"], &root["settings"]) };
+    if !blocks.is_array() {
+        return Err(json::Error::WrongType("timeline should be an array of blocks or an object with settings and blocks".into()));
+    }
+    let mut timeline = Timeline::new(settings["fps"].as_f32().filter(|fps| *fps >= 1.0).unwrap_or(30.0));
+    for block in blocks.members() {
+        // blocks without length would leave the caret on no block, the slider starts at 1 ms too
+        let duration = Duration::from_millis(block["duration"].as_u32().unwrap_or(3000).max(1));
+        let transition = Duration::from_millis(block["transition"].as_u32().unwrap_or(0));
         let graph = load_graph(&block["graph"])?;
-        timeline.blocks.push((duration, graph));
+        timeline.blocks.push(Block { duration, transition, content: graph });
+    }
+    Ok((timeline, load_settings(settings)))
+}
+
+// timelines in .ron files use the serde derives, everything else is json
+#[cfg(feature = "ron")]
+fn is_ron(path: &std::path::Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ron"))
+}
+
+// the settings and the timeline of a .ron file
+#[cfg(feature = "ron")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TimelineFile {
+    settings: VideoSettings,
+    timeline: Timeline<Graph<NodeType>>,
+}
 
+fn read_timeline(path: &std::path::Path, raw: &str) -> Result<(Timeline<Graph<NodeType>>, VideoSettings), String> {
+    #[cfg(feature = "ron")]
+    if is_ron(path) {
+        // older .ron files hold only the timeline
+        return ron::from_str::<TimelineFile>(raw)
+            .map(|file| (file.timeline, file.settings))
+            .or_else(|error| ron::from_str(raw).map(|timeline| (timeline, VideoSettings::default())).map_err(|_| error.to_string()))
+            .map(|(mut timeline, settings)| {
+                // same as json, blocks are at least 1 ms long
+                for block in &mut timeline.blocks {
+                    block.duration.millis = block.duration.millis.max(1);
+                }
+                (timeline, settings)
+            });
+    }
+    let _ = path;
+    load_timeline(raw).map_err(|error| error.to_string())
+}
+
+fn write_timeline(path: &std::path::Path, timeline: &Timeline<Graph<NodeType>>, settings: &VideoSettings) -> Result<String, String> {
+    #[cfg(feature = "ron")]
+    if is_ron(path) {
+        let file = TimelineFile { settings: settings.clone(), timeline: timeline.clone() };
+        return ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).map_err(|error| error.to_string());
     }
-    Ok(timeline)
+    let _ = path;
+    save_timeline(timeline, settings).map(|root| root.pretty(2)).map_err(|error| error.to_string())
 }
 
+// file extensions offered by the open and save dialogs
+const TIMELINE_EXTENSIONS: &[&str] = if cfg!(feature = "ron") { &["json", "ron"] } else { &["json"] };
+
 fn create_graph() -> Graph<NodeType> {
     let mut graph = Graph::new();
-    graph.nodes.push(NodeType::Output);
+    graph.add_node(NodeType::Output);
     graph
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 struct VideoSettings {
     resolution: [usize; 2],
+    // smooth edges in every node that fills shapes, off trades quality for speed
+    anti_alias: bool,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        Self { resolution: [320, 200], anti_alias: true }
+    }
+}
+
+fn load_settings(root: &json::JsonValue) -> VideoSettings {
+    let default = VideoSettings::default();
+    let width = root["width"].as_usize().filter(|width| *width > 0).unwrap_or(default.resolution[0]);
+    let height = root["height"].as_usize().filter(|height| *height > 0).unwrap_or(default.resolution[1]);
+    let anti_alias = root["anti_alias"].as_bool().unwrap_or(default.anti_alias);
+    VideoSettings { resolution: [width, height], anti_alias }
+}
+
+fn save_settings(settings: &VideoSettings, fps: f32) -> json::JsonValue {
+    json::object!{width: settings.resolution[0], height: settings.resolution[1], anti_alias: settings.anti_alias, fps: fps}
+}
+
+impl VideoSettings {
+    // resolution as pixmap dimensions
+    fn size(&self) -> (u32, u32) {
+        (self.resolution[0] as u32, self.resolution[1] as u32)
+    }
+    fn empty_pixmap(&self) -> Pixmap {
+        let (width, height) = self.size();
+        Pixmap::new(width, height).unwrap()
+    }
+}
+
+// maximum number of graph edits that can be undone
+const UNDO_DEPTH: usize = 64;
+
+// graph snapshots together with the index of their block
+#[derive(Default)]
+struct History {
+    undo_stack: Vec<(usize, Graph<NodeType>)>,
+    redo_stack: Vec<(usize, Graph<NodeType>)>,
+}
+impl History {
+    // records the graph of a block as it was before an edit
+    fn push(&mut self, snapshot: (usize, Graph<NodeType>)) {
+        self.undo_stack.push(snapshot);
+        if self.undo_stack.len() > UNDO_DEPTH {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+    fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+    fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+    fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+    fn undo(&mut self, timeline: &mut Timeline<Graph<NodeType>>) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            if let Some(replaced) = restore(timeline, snapshot) {
+                self.redo_stack.push(replaced);
+            }
+        }
+    }
+    fn redo(&mut self, timeline: &mut Timeline<Graph<NodeType>>) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            if let Some(replaced) = restore(timeline, snapshot) {
+                self.undo_stack.push(replaced);
+            }
+        }
+    }
+}
+
+// replaces a block graph with a snapshot, returning a snapshot of what was replaced
+fn restore(timeline: &mut Timeline<Graph<NodeType>>, (index, graph): (usize, Graph<NodeType>)) -> Option<(usize, Graph<NodeType>)> {
+    let block = timeline.blocks.get_mut(index)?;
+    Some((index, std::mem::replace(&mut block.content, graph)))
+}
+
+// what happens when playback reaches the end of the timeline
+#[derive(Clone, Copy, PartialEq)]
+enum Playback {
+    Once,
+    Loop,
+}
+
+impl Playback {
+    const ALL: [Playback; 2] = [Playback::Once, Playback::Loop];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Playback::Once => "once",
+            Playback::Loop => "loop",
+        }
+    }
+}
+
+// size of the output preview
+#[derive(Clone, Copy, PartialEq)]
+enum PreviewZoom {
+    // as large as the window allows
+    Fit,
+    // texels per screen pixel
+    Pixels(u32),
+}
+
+impl PreviewZoom {
+    const ALL: [PreviewZoom; 4] = [PreviewZoom::Fit, PreviewZoom::Pixels(1), PreviewZoom::Pixels(2), PreviewZoom::Pixels(4)];
+
+    fn name(&self) -> String {
+        match self {
+            PreviewZoom::Fit => "fit".into(),
+            PreviewZoom::Pixels(factor) => format!("{}%", 100 * factor),
+        }
+    }
+    // size in points of a preview of the given texture size
+    fn size(&self, texture: Vec2, available: Vec2, pixels_per_point: f32) -> Vec2 {
+        match self {
+            PreviewZoom::Fit => {
+                let scale = (available.x / texture.x).min(available.y / texture.y);
+                if scale.is_finite() { texture * scale.max(0.0) } else { texture }
+            },
+            PreviewZoom::Pixels(factor) => texture * *factor as f32 / pixels_per_point,
+        }
+    }
 }
 
 pub struct PixelLab {
@@ -337,6 +1370,34 @@ pub struct PixelLab {
     output_texture: TextureHandle,
     timeline: Timeline<Graph<NodeType>>,
     play: bool,
+    playback: Playback,
+    // preview speed multiplier, exports always use the timeline fps
+    speed: f32,
+    history: History,
+    // filter text of the node creation menu
+    node_filter: String,
+    export_directory: String,
+    export: Option<Export>,
+    show_settings: bool,
+    // file the timeline was last opened from or saved to
+    current_file: Option<PathBuf>,
+    // where file dialogs start, the directory of the last picked file
+    last_directory: Option<PathBuf>,
+    // shown in a dialog until dismissed
+    error_message: Option<String>,
+    // outcome of the last one-off action, shown in the bottom panel
+    status: String,
+    renderer: Renderer,
+    onion_skin: OnionSkin,
+    // shown behind the output so transparency is visible
+    checkerboard: bool,
+    zoom: PreviewZoom,
+    // render key of the last requested frame
+    requested: u64,
+    // last finished frame
+    rendered: Option<Frame>,
+    // value of the last hovered pin
+    inspection: Option<Inspection>,
 }
 
 impl PixelLab {
@@ -347,63 +1408,282 @@ impl PixelLab {
 
         let fps = 30.0;
         let mut timeline = Timeline::new(fps);
+        let mut video_settings = VideoSettings::default();
+        let mut error_message = None;
+        let mut playback = Playback::Once;
+        let mut export_directory = "frames".to_string();
+        let mut current_file = None;
+        let mut last_directory = None;
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
         if let Some(storage) = cc.storage {
             //return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
             if let Some(raw) = storage.get_string("timeline_json") {
-                println!("{}", raw);
-                timeline = load_timeline(&raw).unwrap();
+                // the settings are restored on their own below
+                match load_timeline(&raw) {
+                    Ok((loaded, _)) => timeline = loaded,
+                    Err(error) => error_message = Some(format!("could not restore timeline: {}", error)),
+                }
+            }
+            if let Some(raw) = storage.get_string("settings_json") {
+                if let Ok(root) = json::parse(&raw) {
+                    video_settings = load_settings(&root);
+                    timeline.fps = root["fps"].as_f32().unwrap_or(fps);
+                    playback = Playback::ALL.into_iter().find(|p| root["playback"] == p.name()).unwrap_or(playback);
+                }
+            }
+            if let Some(raw) = storage.get_string("files_json") {
+                if let Ok(root) = json::parse(&raw) {
+                    export_directory = root["export_directory"].as_str().unwrap_or(&export_directory).to_string();
+                    current_file = root["current_file"].as_str().map(PathBuf::from);
+                    last_directory = root["last_directory"].as_str().map(PathBuf::from);
+                }
             }
         }
 
-        let resolution = [320, 200];
         let output_texture = cc.egui_ctx.load_texture(
             "output",
-            ImageData::Color(Arc::new(ColorImage::new(resolution, Color32::TRANSPARENT))),
-            TextureOptions::default(),
+            ImageData::Color(Arc::new(ColorImage::new(video_settings.resolution, Color32::TRANSPARENT))),
+            TextureOptions::NEAREST,
         );
         let mut app = PixelLab {
-            video_settings: VideoSettings { resolution, },
+            video_settings,
             output_texture,
             timeline,
             play: false,
+            playback,
+            speed: 1.0,
+            history: History::default(),
+            node_filter: String::new(),
+            export_directory,
+            export: None,
+            show_settings: false,
+            current_file,
+            last_directory,
+            error_message,
+            status: String::new(),
+            renderer: Renderer::new(cc.egui_ctx.clone()),
+            onion_skin: OnionSkin { frames: 0, opacity: 0.5 },
+            checkerboard: true,
+            zoom: PreviewZoom::Pixels(1),
+            requested: 0,
+            rendered: None,
+            inspection: None,
         };
 
         // add some stuff on the timeline, if empty
         if app.timeline.blocks.is_empty() {
-            app.timeline.blocks.push((Duration::from_secs(3.0), create_graph()));
+            app.timeline.blocks.push(Block::new(Duration::from_secs(3.0), create_graph()));
         }
 
         app
     }
     fn graph(&mut self) -> &mut Graph<NodeType> {
         let index = self.timeline.selected_index().unwrap();
-        &mut self.timeline.blocks[index].1
+        &mut self.timeline.blocks[index].content
     }
     fn add_node(&mut self, node: NodeType) {
-        self.graph().nodes.push(node);
+        if let Some(snapshot) = self.snapshot() {
+            self.push_undo(snapshot);
+        }
+        self.graph().add_node(node);
+    }
+    // copies the graph of the selected block
+    fn snapshot(&self) -> Option<(usize, Graph<NodeType>)> {
+        let index = self.timeline.selected_index()?;
+        Some((index, self.timeline.blocks[index].content.clone()))
+    }
+    fn push_undo(&mut self, snapshot: (usize, Graph<NodeType>)) {
+        self.history.push(snapshot);
+    }
+    // a file dialog starting in the last used directory
+    #[cfg(not(target_arch = "wasm32"))]
+    fn file_dialog(&self) -> rfd::FileDialog {
+        let dialog = rfd::FileDialog::new();
+        match &self.last_directory {
+            Some(directory) => dialog.set_directory(directory),
+            None => dialog,
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn pick(&mut self, path: Option<PathBuf>) -> Option<PathBuf> {
+        if let Some(directory) = path.as_ref().and_then(|path| path.parent()) {
+            self.last_directory = Some(directory.to_path_buf());
+        }
+        path
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_file(&mut self) {
+        let path = self.file_dialog().add_filter("timeline", TIMELINE_EXTENSIONS).pick_file();
+        if let Some(path) = self.pick(path) {
+            let loaded = std::fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|raw| read_timeline(&path, &raw));
+            match loaded {
+                Ok((mut timeline, settings)) => {
+                    if timeline.blocks.is_empty() {
+                        timeline.blocks.push(Block::new(Duration::from_secs(3.0), create_graph()));
+                    }
+                    self.timeline = timeline;
+                    self.video_settings = settings;
+                    self.history.clear();
+                    self.current_file = Some(path);
+                },
+                Err(error) => self.error_message = Some(format!("could not open {}: {}", path.display(), error)),
+            }
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_file_as(&mut self) {
+        let path = self.file_dialog().add_filter("timeline", TIMELINE_EXTENSIONS).save_file();
+        if let Some(path) = self.pick(path) {
+            self.save_file_to(path);
+        }
+    }
+    // saves to the current file, asking for one if there is none yet
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_file(&mut self) {
+        match self.current_file.clone() {
+            Some(path) => self.save_file_to(path),
+            None => self.save_file_as(),
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_file_to(&mut self, path: PathBuf) {
+        let saved = write_timeline(&path, &self.timeline, &self.video_settings)
+            .and_then(|raw| std::fs::write(&path, raw).map_err(|error| error.to_string()));
+        match saved {
+            Ok(()) => self.current_file = Some(path),
+            Err(error) => self.error_message = Some(format!("could not save {}: {}", path.display(), error)),
+        }
+    }
+    // saves the frame under the caret, evaluated again rather than read back from the texture
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_frame(&mut self) {
+        let path = self.file_dialog().add_filter("png", &["png"]).save_file();
+        let Some(path) = self.pick(path) else {
+            return;
+        };
+        let pixmap = render_at(&self.timeline, &self.timeline.caret, &self.video_settings, &mut Vec::new())
+            .unwrap_or_else(|| self.video_settings.empty_pixmap());
+        self.status = match pixmap.save_png(&path) {
+            Ok(()) => format!("saved frame to {}", path.display()),
+            Err(error) => format!("could not save frame to {}: {}", path.display(), error),
+        };
+    }
+    // saves the frame under the caret as vector shapes
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_svg(&mut self) {
+        let path = self.file_dialog().add_filter("svg", &["svg"]).save_file();
+        let Some(path) = self.pick(path) else {
+            return;
+        };
+        let saved = svg_at(&self.timeline, &self.timeline.caret, &self.video_settings)
+            .and_then(|svg| std::fs::write(&path, svg).map_err(|error| error.to_string()));
+        self.status = match saved {
+            Ok(()) => format!("saved frame to {}", path.display()),
+            Err(error) => format!("could not save svg to {}: {}", path.display(), error),
+        };
+    }
+    // ghosts would only flicker during playback
+    fn shown_onion_skin(&self) -> Option<OnionSkin> {
+        Some(self.onion_skin).filter(|_| !self.play)
+    }
+    fn undo(&mut self) {
+        self.history.undo(&mut self.timeline);
+    }
+    fn redo(&mut self) {
+        self.history.redo(&mut self.timeline);
     }
 }
 
 
 // runs the pipeline
-fn resolve(nodes: &Graph<NodeType>, node_index: usize, pin_index: usize, t: f32) -> PinValue {
+// evaluation problems by node index
+type Diagnostics = Vec<(usize, String)>;
+
+// problems with the structure of a graph, found without evaluating it.
+// pixmap and color field inputs have no useful default, so they should be connected
+fn validate(graph: &Graph<NodeType>) -> Vec<(Option<usize>, String)> {
+    let mut warnings = Vec::new();
+    let outputs: Vec<usize> = (0..graph.nodes.len()).filter(|index| matches!(graph.nodes[*index].widget, NodeType::Output)).collect();
+    if outputs.is_empty() {
+        warnings.push((None, "there is no output node".to_string()));
+    }
+    for index in outputs.iter().skip(1) {
+        warnings.push((Some(*index), "only the first output node is rendered".to_string()));
+    }
+    for (node_index, node) in graph.nodes.iter().enumerate() {
+        let inputs = graph.inputs_for(node_index);
+        for (pin, link) in node.widget.in_pins().iter().zip(&inputs) {
+            if link.is_some() {
+                continue;
+            }
+            let message = match (&node.widget, pin.pin_type) {
+                (NodeType::Output, _) => "nothing is connected to the output".to_string(),
+                (_, PinType::Pixmap | PinType::ColorField) => format!("input {} is not connected", pin.name.as_deref().unwrap_or("")),
+                _ => continue,
+            };
+            warnings.push((Some(node_index), message));
+        }
+        let used = graph.links.iter().any(|(from, _)| from.node_index == node_index);
+        if !node.widget.out_pins().is_empty() && !used {
+            warnings.push((Some(node_index), "the output goes nowhere".to_string()));
+        }
+    }
+    warnings
+}
+
+fn resolve(nodes: &Graph<NodeType>, node_index: usize, pin_index: usize, t: f32, settings: &VideoSettings, diagnostics: &mut Diagnostics) -> PinValue {
     // 1. collect all input pins
     let input_pins = nodes.inputs_for(node_index);
     // 2. resolve respective output pins
     let input_values: Vec<_> = input_pins
         .iter()
-        .map(|pin_id| resolve(nodes, pin_id.node_index, pin_id.pin_index, t))
+        .map(|pin_id| match pin_id {
+            Some(pin_id) => resolve(nodes, pin_id.node_index, pin_id.pin_index, t, settings, diagnostics),
+            None => PinValue::None,
+        })
         .collect();
-    // 3. call this nodes callable
-    nodes.nodes[node_index].evaluate(input_values, pin_index, t)
+    // 3. call this nodes callable, muted nodes pass their first input of the same type through
+    let node = &nodes.nodes[node_index];
+    if node.muted {
+        let output_type = node.widget.out_pins().get(pin_index).map(|pin| pin.pin_type);
+        let input = node.widget.in_pins().iter().position(|pin| Some(pin.pin_type) == output_type || pin.pin_type == PinType::Any);
+        return input.and_then(|input| input_values.into_iter().nth(input)).unwrap_or(PinValue::None);
+    }
+    let mut errors = Vec::new();
+    let value = node.widget.evaluate(input_values, pin_index, t, settings, &mut errors);
+    // nodes feeding several inputs are evaluated more than once
+    for error in errors {
+        if !diagnostics.iter().any(|(index, message)| *index == node_index && *message == error) {
+            diagnostics.push((node_index, error));
+        }
+    }
+    value
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+struct Block<T> {
+    duration: Duration,
+    // crossfade from the previous block over the start of this one
+    transition: Duration,
+    content: T,
 }
 
+impl<T> Block<T> {
+    fn new(duration: Duration, content: T) -> Self {
+        Self { duration, transition: Duration::from_millis(0), content }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 struct Timeline<T> {
     caret: Instant,
     fps: f32,
-    blocks: Vec<(Duration, T)>,
+    blocks: Vec<Block<T>>,
 }
 
 impl<T> Timeline<T> {
@@ -411,12 +1691,12 @@ impl<T> Timeline<T> {
         Self { caret: Instant::zero(), fps, blocks: Vec::new(), }
     }
     fn duration(&self) -> Duration {
-        self.blocks.iter().map(|(duration, _)| duration).sum()
+        self.blocks.iter().map(|block| &block.duration).sum()
     }
     fn selected_index(&self) -> Option<usize> {
         let mut start = Instant::zero();
-        for (index, (duration, _)) in &mut self.blocks.iter().enumerate() {
-            let end = start.after(duration);
+        for (index, block) in self.blocks.iter().enumerate() {
+            let end = start.after(&block.duration);
             if self.caret.millis < end.millis {
                 return Some(index);
             }
@@ -425,8 +1705,35 @@ impl<T> Timeline<T> {
         None
     }
     fn cap_caret(&mut self) {
-        if self.caret.millis > self.duration().millis {
-            self.caret = Instant::zero().after(&Duration::from_millis(self.duration().millis - 1));
+        let duration = self.duration();
+        if duration.millis == 0 {
+            self.caret = Instant::zero();
+        } else if self.caret.millis >= duration.millis {
+            self.caret = Instant::zero().after(&(&duration - &Duration::from_millis(1)));
+        }
+    }
+    fn block_start(&self, index: usize) -> Instant {
+        Instant::zero().after(&self.blocks[..index].iter().map(|block| &block.duration).sum())
+    }
+    // moves a block while keeping the caret on the same block
+    fn move_block(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        let selected = self.selected_index().map(|index| (index, self.caret.duration_since(&self.block_start(index))));
+        let block = self.blocks.remove(from);
+        self.blocks.insert(to, block);
+        if let Some((index, offset)) = selected {
+            let index = if index == from {
+                to
+            } else if from < index && index <= to {
+                index - 1
+            } else if to <= index && index < from {
+                index + 1
+            } else {
+                index
+            };
+            self.caret = self.block_start(index).after(&offset);
         }
     }
     fn delete_selected(&mut self) {
@@ -435,12 +1742,46 @@ impl<T> Timeline<T> {
             self.cap_caret();
         }
     }
-    fn selected_mut(&mut self) -> Option<&mut (Duration, T)> {
+    fn selected_mut(&mut self) -> Option<&mut Block<T>> {
         self.selected_index().map(|index| &mut self.blocks[index])
     }
+    // moves the caret a number of frames, staying within the timeline
+    fn step_frames(&mut self, frames: i32) {
+        // steps from the frame under the caret so rounding never accumulates
+        let frame = self.caret.duration_since(&Instant::zero()).as_frames(self.fps);
+        self.caret = self.frame_instant(frame.saturating_add_signed(frames));
+        self.cap_caret();
+    }
     fn show_ticks(&mut self, ui: &mut Ui) -> Response {
         let desired_size = Vec2::new(ui.available_width(), 25.0);
-        let (rect, response) = ui.allocate_at_least(desired_size, Sense::drag());
+        let (rect, response) = ui.allocate_at_least(desired_size, Sense::click_and_drag());
+
+        // arrow keys scrub while the timeline has focus
+        if response.clicked() || response.drag_started() {
+            response.request_focus();
+        }
+        if response.has_focus() {
+            ui.memory_mut(|memory| memory.set_focus_lock_filter(response.id, egui::EventFilter { horizontal_arrows: true, ..Default::default() }));
+            let (left, right, home, end) = ui.input(|i| (
+                i.key_pressed(Key::ArrowLeft),
+                i.key_pressed(Key::ArrowRight),
+                i.key_pressed(Key::Home),
+                i.key_pressed(Key::End),
+            ));
+            if left {
+                self.step_frames(-1);
+            }
+            if right {
+                self.step_frames(1);
+            }
+            if home {
+                self.caret = Instant::zero();
+            }
+            if end {
+                self.caret = Instant::zero().after(&self.duration());
+                self.cap_caret();
+            }
+        }
 
         let frame_duration = Duration::from_secs(1.0 / self.fps);
         let total_duration = self.duration();
@@ -453,13 +1794,22 @@ impl<T> Timeline<T> {
             let y = rect.top()..=rect.top() + rect.height();
             painter.vline(x, y, Stroke::new(1.0, Color32::DARK_GRAY));
         }
+        // label whole seconds with timecodes
+        if total_duration.as_millis() > 0 {
+            for second in 0..=total_duration.as_millis() / 1000 {
+                let duration = Duration::from_secs(second as f32);
+                let x = rect.left() + rect.width() * duration.as_millis() as f32 / total_duration.as_millis() as f32;
+                painter.vline(x, rect.top()..=rect.bottom(), Stroke::new(1.0, Color32::GRAY));
+                painter.text(Pos2::new(x + 2.0, rect.top()), egui::Align2::LEFT_TOP, duration.format(self.fps), egui::FontId::monospace(9.0), Color32::GRAY);
+            }
+        }
 
         // handle caret drag
         if let Some(pointer) = response.interact_pointer_pos() {
             self.caret.millis = (total_duration.as_millis() as f32 * pointer.x / rect.width()) as u32;
         }
         // draw caret
-        let x = rect.left() + self.caret.millis as f32 * rect.width() / total_duration.as_millis() as f32;
+        let x = rect.left() + self.global_time() * rect.width();
         painter.vline(x, rect.bottom_up_range(), Stroke::new(1.0, Color32::LIGHT_GRAY));
 
         response
@@ -467,21 +1817,576 @@ impl<T> Timeline<T> {
     
     // return global time as 0-1
     fn global_time(&self) -> f32 {
-        self.caret.millis as f32 / self.duration().as_millis() as f32
+        let duration = self.duration();
+        if duration.millis == 0 {
+            return 0.0;
+        }
+        self.caret.millis as f32 / duration.as_millis() as f32
     }
 
-    // returns the time in the block as 0-1
-    fn local_time(&self) -> f32 {
+    // returns the index of the block at the given instant and the time in that block as 0-1
+    fn block_at(&self, instant: &Instant) -> Option<(usize, f32)> {
         let mut start = Instant::zero();
-        for (duration, _) in &self.blocks {
-            let end = start.after(duration);
-            if self.caret.millis < end.millis {
-                return (self.caret.millis - start.millis) as f32 / duration.millis as f32;
+        for (index, block) in self.blocks.iter().enumerate() {
+            let end = start.after(&block.duration);
+            if instant.millis < end.millis {
+                return Some((index, instant.duration_since(&start).millis as f32 / block.duration.millis as f32));
             }
             start = end;
         }
-        0.0
+        None
     }
+
+    // number of frames needed to cover the whole timeline
+    fn frame_count(&self) -> u32 {
+        (self.duration().as_millis() as f32 * self.fps / 1000.0).ceil() as u32
+    }
+
+    // the instant at which the given frame is shown, rounded up so it maps back to the same frame
+    fn frame_instant(&self, frame_index: u32) -> Instant {
+        Instant::zero().after(&Duration::from_millis((frame_index as f32 * 1000.0 / self.fps.max(1.0)).ceil() as u32))
+    }
+}
+
+// a hovered pin value, kept until the graph or the time changes
+struct Inspection {
+    // source pin and hash of what its value depends on
+    key: (PinId, u64),
+    value: PinValue,
+    // image of pixmaps and color fields
+    texture: Option<TextureHandle>,
+}
+
+// the output pin whose image is shown as the thumbnail of a node
+fn thumbnail_pin(widget: &NodeType) -> Option<usize> {
+    widget.out_pins().iter().position(|pin| pin.pin_type == PinType::Pixmap)
+}
+
+// shows the value of an output pin, or of the output feeding an input pin
+fn inspect_pin(ui: &mut Ui, graph: &Graph<NodeType>, pin_id: PinId, t: f32, settings: &VideoSettings, cache: &mut Option<Inspection>) {
+    let source = match pin_id.direction {
+        PinDirection::Output => Some(pin_id),
+        PinDirection::Input => graph.links.iter().find(|(_, to)| *to == pin_id).map(|(from, _)| *from),
+    };
+    let Some(source) = source else {
+        ui.weak("not connected");
+        return;
+    };
+    let show_texture = |ui: &mut Ui, texture: &TextureHandle| {
+        let size = texture.size_vec2();
+        ui.add(egui::Image::from_texture(texture).fit_to_exact_size(size * (64.0 / size.y)));
+    };
+    // the renderer already made the image of thumbnail pins
+    let node = &graph.nodes[source.node_index];
+    if let Some(texture) = node.thumbnail.as_ref().filter(|_| thumbnail_pin(&node.widget) == Some(source.pin_index)) {
+        ui.label(format!("{}x{}", texture.size()[0], texture.size()[1]));
+        show_texture(ui, texture);
+        return;
+    }
+    let mut hasher = DefaultHasher::new();
+    (t.to_bits(), settings.resolution, settings.anti_alias).hash(&mut hasher);
+    hash_graph(graph, &mut hasher);
+    let key = (source, hasher.finish());
+    if !cache.as_ref().is_some_and(|inspection| inspection.key == key) {
+        let value = resolve(graph, source.node_index, source.pin_index, t, settings, &mut Vec::new());
+        let load = |pixmap: &Pixmap| ui.ctx().load_texture(
+            "pin thumbnail",
+            ColorImage::from_rgba_premultiplied([pixmap.width() as usize, pixmap.height() as usize], pixmap.data()),
+            TextureOptions::NEAREST,
+        );
+        let (width, height) = settings.size();
+        let texture = match &value {
+            PinValue::Pixmap(pixmap) => Some(load(pixmap)),
+            PinValue::ColorField(field) => rasterize(field.as_ref(), width, height).map(|pixmap| load(&pixmap)),
+            _ => None,
+        };
+        *cache = Some(Inspection { key, value, texture });
+    }
+    let Some(inspection) = cache.as_ref() else { return };
+    let thumbnail = |ui: &mut Ui| {
+        if let Some(texture) = &inspection.texture {
+            show_texture(ui, texture);
+        }
+    };
+    match &inspection.value {
+        PinValue::None => { ui.weak("nothing"); },
+        PinValue::Float(value) => { ui.label(format!("{:.3}", value)); },
+        PinValue::Vec2(value) => { ui.label(format!("({:.2}, {:.2})", value.x, value.y)); },
+        PinValue::String(value) => { ui.label(format!("\"{}\"", value)); },
+        PinValue::Color(color) => {
+            let color = color.to_color_u8();
+            let color32 = Color32::from_rgba_unmultiplied(color.red(), color.green(), color.blue(), color.alpha());
+            ui.horizontal(|ui| {
+                let (rect, _) = ui.allocate_exact_size(Vec2::splat(16.0), Sense::hover());
+                ui.painter().rect_filled(rect, 2.0, color32);
+                ui.label(color32.to_hex());
+            });
+        },
+        PinValue::Transform(ts) => {
+            ui.monospace(format!("{:7.2} {:7.2} {:7.2}\n{:7.2} {:7.2} {:7.2}", ts.sx, ts.kx, ts.tx, ts.ky, ts.sy, ts.ty));
+        },
+        PinValue::Pixmap(pixmap) => {
+            ui.label(format!("{}x{}", pixmap.width(), pixmap.height()));
+            thumbnail(ui);
+        },
+        PinValue::ColorField(_) => thumbnail(ui),
+        PinValue::Palette(colors) => {
+            ui.label(format!("{} colors", colors.len()));
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing = Vec2::splat(2.0);
+                for color in colors {
+                    let color = color.to_color_u8();
+                    let (rect, _) = ui.allocate_exact_size(Vec2::splat(12.0), Sense::hover());
+                    ui.painter().rect_filled(rect, 0.0, Color32::from_rgba_unmultiplied(color.red(), color.green(), color.blue(), color.alpha()));
+                }
+            });
+        },
+    }
+}
+
+// the first output node is the one rendered
+fn output_index(graph: &Graph<NodeType>) -> Option<usize> {
+    graph.nodes.iter().position(|node| matches!(node.widget, NodeType::Output))
+}
+
+// evaluates the output of the block showing at the given instant, crossfading from the previous block
+fn render_at(timeline: &Timeline<Graph<NodeType>>, instant: &Instant, settings: &VideoSettings, diagnostics: &mut Diagnostics) -> Option<Pixmap> {
+    let (index, t) = timeline.block_at(instant)?;
+    let block = &timeline.blocks[index];
+    let mut pixmap = resolve(&block.content, output_index(&block.content)?, 0, t, settings, diagnostics).pixmap()?;
+    let offset = instant.duration_since(&timeline.block_start(index));
+    if index > 0 && offset.millis < block.transition.millis {
+        // the previous block holds its last frame while fading out
+        // its diagnostics belong to another graph, so they are not reported
+        let previous_graph = &timeline.blocks[index - 1].content;
+        let previous = output_index(previous_graph).and_then(|output| resolve(previous_graph, output, 0, 1.0, settings, &mut Vec::new()).pixmap());
+        if let Some(previous) = previous {
+            let progress = offset.millis as f32 / block.transition.millis as f32;
+            let paint = PixmapPaint { opacity: 1.0 - progress, ..PixmapPaint::default() };
+            pixmap.draw_pixmap(0, 0, previous.as_ref(), &paint, Transform::identity(), None);
+        }
+    }
+    Some(pixmap)
+}
+
+// the graph showing at the instant, the index of the node connected to its output and the block time
+fn output_source_index<'a>(timeline: &'a Timeline<Graph<NodeType>>, instant: &Instant) -> Result<(&'a Graph<NodeType>, usize, f32), String> {
+    let (index, t) = timeline.block_at(instant).ok_or("the timeline is empty")?;
+    let graph = &timeline.blocks[index].content;
+    let output = output_index(graph).ok_or("there is no output node")?;
+    let source = graph.inputs_for(output).into_iter().next().flatten().ok_or("nothing is connected to the output")?;
+    Ok((graph, source.node_index, t))
+}
+
+fn evaluate_inputs(graph: &Graph<NodeType>, node_index: usize, t: f32, settings: &VideoSettings) -> Vec<PinValue> {
+    graph.inputs_for(node_index).into_iter()
+        .map(|pin_id| pin_id.map_or(PinValue::None, |pin_id| resolve(graph, pin_id.node_index, pin_id.pin_index, t, settings, &mut Vec::new())))
+        .collect()
+}
+
+// the node connected to the output showing at the instant, with its inputs evaluated
+fn output_source<'a>(timeline: &'a Timeline<Graph<NodeType>>, instant: &Instant, settings: &VideoSettings) -> Result<(&'a Node<NodeType>, Vec<PinValue>), String> {
+    let (graph, source, t) = output_source_index(timeline, instant)?;
+    Ok((&graph.nodes[source], evaluate_inputs(graph, source, t, settings)))
+}
+
+// the grid of the hex node drawing the output at the instant, for picking hexes in the preview
+fn output_hex_grid(timeline: &Timeline<Graph<NodeType>>, instant: &Instant, settings: &VideoSettings) -> Option<HexGrid> {
+    let (graph, source, t) = output_source_index(timeline, instant).ok()?;
+    let node = &graph.nodes[source];
+    match node.widget {
+        // other outputs don't need their inputs evaluated again
+        NodeType::Hex(orientation, anti_alias) if !node.muted => {
+            Some(hex_grid(evaluate_inputs(graph, source, t, settings).into_iter(), orientation, anti_alias, settings).0)
+        },
+        _ => None,
+    }
+}
+
+// the frame at the given instant as an svg document, for outputs drawn by a node with vector shapes
+// transitions are not crossfaded, the block showing at the instant is exported on its own
+fn svg_at(timeline: &Timeline<Graph<NodeType>>, instant: &Instant, settings: &VideoSettings) -> Result<String, String> {
+    let (node, inputs) = output_source(timeline, instant, settings)?;
+    let (elements, (width, height)) = node.widget.svg(inputs, settings)
+        .filter(|_| !node.muted)
+        .ok_or_else(|| format!("{} can't be exported as svg", node.widget.title()))??;
+    Ok(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n{elements}</svg>\n"
+    ))
+}
+
+// gray squares showing where the output is transparent
+fn paint_checkerboard(painter: egui::Painter, rect: egui::Rect) {
+    let size = 8.0;
+    painter.rect_filled(rect, 0.0, Color32::from_gray(160));
+    let (columns, rows) = ((rect.width() / size).ceil() as usize, (rect.height() / size).ceil() as usize);
+    for row in 0..rows {
+        for column in (row % 2..columns).step_by(2) {
+            let min = rect.min + Vec2::new(column as f32, row as f32) * size;
+            painter.rect_filled(egui::Rect::from_min_size(min, Vec2::splat(size)), 0.0, Color32::from_gray(100));
+        }
+    }
+}
+
+// feeds formatted text straight into a hasher
+struct HashWriter<'a>(&'a mut DefaultHasher);
+impl std::fmt::Write for HashWriter<'_> {
+    fn write_str(&mut self, text: &str) -> std::fmt::Result {
+        self.0.write(text.as_bytes());
+        Ok(())
+    }
+}
+
+// hash of everything the output frame depends on, node positions and the view excluded
+// only the blocks the frame and its ghosts can show are hashed, along with the files their nodes read
+fn render_key(timeline: &Timeline<Graph<NodeType>>, settings: &VideoSettings, onion_skin: Option<OnionSkin>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let (frames, opacity) = onion_skin.map_or((0, 0.0), |skin| (skin.frames, skin.opacity));
+    (timeline.caret.millis, timeline.fps.to_bits(), settings.resolution, settings.anti_alias, frames, opacity.to_bits()).hash(&mut hasher);
+    // the block lengths decide which block shows when
+    for block in &timeline.blocks {
+        (block.duration.millis, block.transition.millis).hash(&mut hasher);
+    }
+    let reach = (frames as f32 * 1000.0 / timeline.fps.max(1.0)).ceil() as u32;
+    let block_index = |millis: u32| timeline.block_at(&Instant { millis }).map(|(index, _)| index);
+    let Some(first) = block_index(timeline.caret.millis.saturating_sub(reach)) else {
+        return hasher.finish();
+    };
+    let last = block_index(timeline.caret.millis.saturating_add(reach)).unwrap_or(timeline.blocks.len() - 1);
+    // a block fades in from the one before it
+    for block in &timeline.blocks[first.saturating_sub(1)..=last] {
+        hash_graph(&block.content, &mut hasher);
+    }
+    hasher.finish()
+}
+
+// the nodes and links of a graph, with the modification time of the files its nodes read
+fn hash_graph(graph: &Graph<NodeType>, hasher: &mut DefaultHasher) {
+    for node in &graph.nodes {
+        let _ = write!(HashWriter(hasher), "{:?} {}", node.widget, node.muted);
+        // files changed on disk are read again
+        if let NodeType::Pixmap(path, ..) | NodeType::Palette(path) = &node.widget {
+            std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok().hash(hasher);
+        }
+    }
+    let _ = write!(HashWriter(hasher), "{:?}", graph.links);
+}
+
+// render key, frame, diagnostics and hex grid of the output, if any, of an evaluation
+type Frame = (u64, Pixmap, Diagnostics, Option<HexGrid>);
+// images produced by the nodes of the graph at the caret, by node index
+type Thumbnails = Vec<(usize, Pixmap)>;
+
+// ghosts of the neighbouring frames drawn under the preview
+#[derive(Clone, Copy)]
+struct OnionSkin {
+    // on each side, none when zero
+    frames: u32,
+    opacity: f32,
+}
+
+struct RenderJob {
+    key: u64,
+    // rendered at its caret
+    timeline: Timeline<Graph<NodeType>>,
+    settings: VideoSettings,
+    onion_skin: Option<OnionSkin>,
+    thumbnails: bool,
+}
+impl RenderJob {
+    fn render(self) -> (Frame, Thumbnails) {
+        let mut diagnostics = Vec::new();
+        let caret = self.timeline.caret;
+        let output = render_at(&self.timeline, &caret, &self.settings, &mut diagnostics)
+            .unwrap_or_else(|| self.settings.empty_pixmap());
+        let output = match self.onion_skin.filter(|skin| skin.frames > 0) {
+            Some(skin) => self.with_onion_skin(output, skin),
+            None => output,
+        };
+        let thumbnails = if self.thumbnails { self.thumbnails() } else { Vec::new() };
+        let grid = output_hex_grid(&self.timeline, &caret, &self.settings);
+        ((self.key, output, diagnostics, grid), thumbnails)
+    }
+    // the first image output of every node that has one
+    fn thumbnails(&self) -> Thumbnails {
+        let Some((index, t)) = self.timeline.block_at(&self.timeline.caret) else {
+            return Vec::new();
+        };
+        let graph = &self.timeline.blocks[index].content;
+        (0..graph.nodes.len())
+            .filter_map(|node_index| {
+                let pin_index = thumbnail_pin(&graph.nodes[node_index].widget)?;
+                let pixmap = resolve(graph, node_index, pin_index, t, &self.settings, &mut Vec::new()).pixmap()?;
+                Some((node_index, pixmap))
+            })
+            .collect()
+    }
+    // previous frames tinted red and next frames green, fading with distance
+    fn with_onion_skin(&self, frame: Pixmap, skin: OnionSkin) -> Pixmap {
+        let frame_millis = (1000.0 / self.timeline.fps) as i64;
+        let end = self.timeline.duration().millis as i64;
+        let mut result = self.settings.empty_pixmap();
+        // the furthest ghosts are drawn first
+        for distance in (1..=skin.frames as i64).rev() {
+            let opacity = skin.opacity * (1.0 - (distance - 1) as f32 / skin.frames as f32);
+            for (direction, factors) in [(-1, [1.0, 0.3, 0.3]), (1, [0.3, 1.0, 0.3])] {
+                let millis = self.timeline.caret.millis as i64 + direction * distance * frame_millis;
+                if !(0..end).contains(&millis) {
+                    continue;
+                }
+                let instant = Instant { millis: millis as u32 };
+                if let Some(ghost) = render_at(&self.timeline, &instant, &self.settings, &mut Vec::new()) {
+                    let paint = PixmapPaint { opacity, ..PixmapPaint::default() };
+                    result.draw_pixmap(0, 0, filters::tint(&ghost, factors).as_ref(), &paint, Transform::identity(), None);
+                }
+            }
+        }
+        result.draw_pixmap(0, 0, frame.as_ref(), &PixmapPaint::default(), Transform::identity(), None);
+        result
+    }
+}
+
+// evaluates frames on a worker thread so slow graphs don't block the editor.
+// jobs only carry plain graphs, the non-send pin values live and die on the worker
+#[cfg(not(target_arch = "wasm32"))]
+struct Renderer {
+    jobs: std::sync::mpsc::Sender<RenderJob>,
+    frames: std::sync::mpsc::Receiver<(Frame, Thumbnails)>,
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl Renderer {
+    fn new(ctx: egui::Context) -> Self {
+        let (jobs, job_receiver) = std::sync::mpsc::channel::<RenderJob>();
+        let (frame_sender, frames) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(mut job) = job_receiver.recv() {
+                // skip jobs that are already outdated
+                while let Ok(newer) = job_receiver.try_recv() {
+                    job = newer;
+                }
+                if frame_sender.send(job.render()).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+        Self { jobs, frames }
+    }
+    fn request(&self, job: RenderJob) {
+        // the worker only stops when the renderer is dropped
+        let _ = self.jobs.send(job);
+    }
+    // the newest finished frame since last asked
+    fn latest(&self) -> Option<(Frame, Thumbnails)> {
+        self.frames.try_iter().last()
+    }
+}
+
+// there are no threads on the web, frames are rendered when requested
+#[cfg(target_arch = "wasm32")]
+struct Renderer {
+    frame: Option<(Frame, Thumbnails)>,
+}
+#[cfg(target_arch = "wasm32")]
+impl Renderer {
+    fn new(_ctx: egui::Context) -> Self {
+        Self { frame: None }
+    }
+    fn request(&mut self, job: RenderJob) {
+        self.frame = Some(job.render());
+    }
+    fn latest(&mut self) -> Option<(Frame, Thumbnails)> {
+        self.frame.take()
+    }
+}
+
+// evaluates the output at the given frame
+fn render_frame(timeline: &Timeline<Graph<NodeType>>, frame_index: u32, settings: &VideoSettings) -> Option<Pixmap> {
+    render_at(timeline, &timeline.frame_instant(frame_index), settings, &mut Vec::new())
+}
+
+// renders a frame at exactly the settings size, frames of another size are centered and padded or cut
+fn export_frame(timeline: &Timeline<Graph<NodeType>>, frame_index: u32, settings: &VideoSettings) -> Pixmap {
+    let pixmap = render_frame(timeline, frame_index, settings).unwrap_or_else(|| settings.empty_pixmap());
+    let (width, height) = settings.size();
+    if (pixmap.width(), pixmap.height()) == (width, height) {
+        return pixmap;
+    }
+    let left = (pixmap.width() as i32 - width as i32) / 2;
+    let top = (pixmap.height() as i32 - height as i32) / 2;
+    filters::crop(&pixmap, left, top, width, height).unwrap_or_else(|| settings.empty_pixmap())
+}
+
+// an animated gif sharing one palette between all frames
+#[cfg(feature = "gif")]
+struct GifTarget {
+    encoder: gif::Encoder<std::fs::File>,
+    // opaque palette colors and their indices, the transparent entry is left out
+    colors: Vec<tiny_skia::ColorU8>,
+    indices: Vec<u8>,
+    transparent: Option<u8>,
+    // palette index of every rgb seen so far
+    lookup: std::collections::HashMap<[u8; 3], u8>,
+}
+
+#[cfg(feature = "gif")]
+impl GifTarget {
+    fn index(&mut self, color: tiny_skia::ColorU8) -> u8 {
+        if let (Some(transparent), 0) = (self.transparent, color.alpha()) {
+            return transparent;
+        }
+        let (colors, indices) = (&self.colors, &self.indices);
+        *self.lookup
+            .entry([color.red(), color.green(), color.blue()])
+            .or_insert_with(|| filters::nearest(colors, color).map_or(0, |nearest| indices[nearest]))
+    }
+}
+
+// where exported frames end up
+enum ExportTarget {
+    // numbered png files in a directory
+    Frames(PathBuf),
+    // a single animated gif
+    #[cfg(feature = "gif")]
+    Gif(Box<GifTarget>),
+}
+
+// an export in progress, rendered one frame per repaint
+struct Export {
+    target: ExportTarget,
+    next_frame: u32,
+    frame_count: u32,
+}
+
+impl Export {
+    fn frames(directory: PathBuf, timeline: &Timeline<Graph<NodeType>>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self::new(ExportTarget::Frames(directory), timeline))
+    }
+    #[cfg(feature = "gif")]
+    fn gif(path: &std::path::Path, timeline: &Timeline<Graph<NodeType>>, settings: &VideoSettings) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        let (width, height) = settings.size();
+        // quantizes a few evenly spaced frames stacked into one image, so all frames share a palette
+        let frame_count = timeline.frame_count();
+        let samples = frame_count.clamp(1, 8).min(u16::MAX as u32 / height.max(1)).max(1);
+        let mut rgba = Vec::new();
+        for sample in 0..samples {
+            rgba.extend(demultiplied_rgba(&export_frame(timeline, sample * frame_count / samples, settings)));
+        }
+        let quantized = gif::Frame::from_rgba_speed(width as u16, (height * samples) as u16, &mut rgba, 10);
+        let palette = quantized.palette.unwrap_or_default();
+        let (indices, colors) = palette.chunks_exact(3)
+            .enumerate()
+            .filter(|(index, _)| quantized.transparent != Some(*index as u8))
+            .map(|(index, rgb)| (index as u8, tiny_skia::ColorU8::from_rgba(rgb[0], rgb[1], rgb[2], 255)))
+            .unzip();
+        let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &palette)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        let target = GifTarget { encoder, colors, indices, transparent: quantized.transparent, lookup: Default::default() };
+        Ok(Self::new(ExportTarget::Gif(Box::new(target)), timeline))
+    }
+    fn new(target: ExportTarget, timeline: &Timeline<Graph<NodeType>>) -> Self {
+        Self { target, next_frame: 0, frame_count: timeline.frame_count() }
+    }
+    fn is_done(&self) -> bool {
+        self.next_frame >= self.frame_count
+    }
+    fn progress(&self) -> f32 {
+        self.next_frame as f32 / self.frame_count.max(1) as f32
+    }
+    // renders and writes the next frame, frames without image output are written transparent
+    fn step(&mut self, timeline: &Timeline<Graph<NodeType>>, settings: &VideoSettings) -> Result<(), Box<dyn std::error::Error>> {
+        let pixmap = export_frame(timeline, self.next_frame, settings);
+        match &mut self.target {
+            ExportTarget::Frames(directory) => {
+                let path = directory.join(format!("frame_{:04}.png", self.next_frame));
+                pixmap.save_png(path)?;
+            },
+            #[cfg(feature = "gif")]
+            ExportTarget::Gif(gif) => {
+                // maps each pixel into the shared palette, fully transparent pixels get the transparent index
+                let pixels: Vec<u8> = pixmap.pixels().iter().map(|pixel| gif.index(pixel.demultiply())).collect();
+                let mut frame = gif::Frame::from_indexed_pixels(pixmap.width() as u16, pixmap.height() as u16, pixels, gif.transparent);
+                frame.delay = (100.0 / timeline.fps).round() as u16;
+                gif.encoder.write_frame(&frame)?;
+            },
+        }
+        self.next_frame += 1;
+        Ok(())
+    }
+}
+
+/// Renders every frame of a timeline json (or ron) file into numbered png files without any ui,
+/// returning the number of frames written. The fps and the width and height in pixels default
+/// to the settings saved in the file when not given.
+pub fn render_timeline(input: &std::path::Path, directory: PathBuf, fps: Option<f32>, resolution: Option<[usize; 2]>) -> Result<u32, Box<dyn std::error::Error>> {
+    let raw = std::fs::read_to_string(input)?;
+    let (mut timeline, mut settings) = read_timeline(input, &raw)?;
+    if let Some(fps) = fps {
+        timeline.fps = fps;
+    }
+    if let Some(resolution) = resolution {
+        if resolution.contains(&0) {
+            return Err(format!("invalid size {}x{}", resolution[0], resolution[1]).into());
+        }
+        settings.resolution = resolution;
+    }
+    let mut export = Export::frames(directory, &timeline)?;
+    while !export.is_done() {
+        export.step(&timeline, &settings)?;
+    }
+    Ok(export.frame_count)
+}
+
+/// Evaluates every frame of a timeline json without writing them anywhere, returning the number
+/// of frames. Times the graph evaluation on its own, see benches/render.rs.
+pub fn evaluate_timeline(raw: &str) -> Result<u32, String> {
+    let (timeline, settings) = load_timeline(raw).map_err(|error| error.to_string())?;
+    let frame_count = timeline.frame_count();
+    for frame_index in 0..frame_count {
+        render_frame(&timeline, frame_index, &settings);
+    }
+    Ok(frame_count)
+}
+
+// straight alpha rgba bytes of a pixmap
+#[cfg(any(feature = "gif", feature = "clipboard"))]
+fn demultiplied_rgba(pixmap: &Pixmap) -> Vec<u8> {
+    pixmap.pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect()
+}
+
+impl Timeline<Graph<NodeType>> {
+    // splits the block under the caret, or inserts a new block before it when the caret is at its start
+    fn insert_at_caret(&mut self, index: usize) {
+        let offset = self.caret.duration_since(&self.block_start(index));
+        if offset.millis == 0 {
+            self.blocks.insert(index, Block::new(Duration::from_secs(3.0), create_graph()));
+            return;
+        }
+        let block = &mut self.blocks[index];
+        let rest = &block.duration - &offset;
+        let graph = block.content.clone();
+        block.duration = offset;
+        self.blocks.insert(index + 1, Block::new(rest, graph));
+    }
+}
+
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+fn copy_to_clipboard(pixmap: &Pixmap) -> Result<(), arboard::Error> {
+    // other applications expect straight alpha
+    let image = arboard::ImageData {
+        width: pixmap.width() as usize,
+        height: pixmap.height() as usize,
+        bytes: demultiplied_rgba(pixmap).into(),
+    };
+    arboard::Clipboard::new()?.set_image(image)
 }
 
 impl Widget for &mut Timeline<Graph<NodeType>> {
@@ -491,12 +2396,21 @@ impl Widget for &mut Timeline<Graph<NodeType>> {
             if self.blocks.len() > 1 && ui.button("delete").clicked() {
                 self.delete_selected();
             }
+            if let Some(index) = self.selected_index() {
+                if ui.button("insert").clicked() {
+                    self.insert_at_caret(index);
+                }
+                if ui.button("duplicate").clicked() {
+                    self.blocks.insert(index + 1, self.blocks[index].clone());
+                }
+            }
             if ui.button("add").clicked() {
                 let duration = Duration::from_secs(3.0);
-                self.blocks.push((duration, create_graph()));
+                self.blocks.push(Block::new(duration, create_graph()));
             }
-            if let Some((duration, _)) = self.selected_mut() {
-                ui.add(egui::Slider::new(&mut duration.millis, 1..=5000));
+            if let Some(block) = self.selected_mut() {
+                ui.add(egui::Slider::new(&mut block.transition.millis, 0..=block.duration.millis).text("fade"));
+                ui.add(egui::Slider::new(&mut block.duration.millis, 1..=5000));
             }
             ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
                 self.show_ticks(ui);
@@ -505,11 +2419,26 @@ impl Widget for &mut Timeline<Graph<NodeType>> {
                     let height = 50.0;
                     let total_width = ui.available_width();
                     let total_duration = self.duration();
-                    for (duration, _) in &self.blocks {
-                        let width = total_width * duration.as_millis() as f32 / total_duration.as_millis() as f32;
-                        ui.group(|ui| {
-                            ui.allocate_exact_size(Vec2::new(width, height), Sense::empty());
+                    let mut rects = Vec::new();
+                    let mut dragged = None;
+                    for (index, block) in self.blocks.iter().enumerate() {
+                        let width = total_width * block.duration.as_millis() as f32 / total_duration.as_millis() as f32;
+                        let group = ui.group(|ui| {
+                            ui.allocate_exact_size(Vec2::new(width, height), Sense::drag()).1
                         });
+                        if group.inner.dragged() || group.inner.drag_stopped() {
+                            dragged = Some((index, group.inner.drag_stopped()));
+                        }
+                        rects.push(group.response.rect);
+                    }
+                    // drag blocks to reorder them
+                    if let (Some((from, released)), Some(pointer)) = (dragged, ui.ctx().pointer_interact_pos()) {
+                        let slot = rects.iter().position(|rect| pointer.x < rect.center().x).unwrap_or(rects.len());
+                        let x = rects.get(slot).map_or_else(|| rects[rects.len() - 1].right(), |rect| rect.left());
+                        ui.painter().vline(x, rects[0].y_range(), Stroke::new(2.0, ui.visuals().selection.bg_fill));
+                        if released {
+                            self.move_block(from, if slot > from { slot - 1 } else { slot });
+                        }
                     }
                 });
             })
@@ -521,17 +2450,50 @@ impl Widget for &mut Timeline<Graph<NodeType>> {
 impl eframe::App for PixelLab {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        if let Ok(raw) = save_timeline(&self.timeline) {
+        if let Ok(raw) = save_timeline(&self.timeline, &self.video_settings) {
             storage.set_string("timeline_json", raw.dump());
             //storage.set_string("graph_json", raw);
         } else {
             println!("could not save timeline");
         }
+        let mut settings = save_settings(&self.video_settings, self.timeline.fps);
+        settings["playback"] = self.playback.name().into();
+        storage.set_string("settings_json", settings.dump());
+        let files = json::object!{
+            export_directory: self.export_directory.as_str(),
+            current_file: self.current_file.as_ref().and_then(|path| path.to_str()),
+            last_directory: self.last_directory.as_ref().and_then(|path| path.to_str()),
+        };
+        storage.set_string("files_json", files.dump());
         //storage.set_string(eframe::APP_KEY, value);
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // check the shift variant first since shift is ignored when not asked for
+        let redo = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z);
+        let undo = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+        if ctx.input_mut(|i| i.consume_shortcut(&redo)) {
+            self.redo();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+            self.undo();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::S))) {
+            self.save_file();
+        }
+        // text fields use these keys themselves
+        let delete = ctx.input(|i| i.key_pressed(Key::Delete) || i.key_pressed(Key::Backspace));
+        if delete && !ctx.wants_keyboard_input() && self.timeline.selected_index().is_some() {
+            // only copy the graph when something is about to be removed
+            if self.graph().nodes.iter().any(|node| node.selected && node.widget.removable()) {
+                if let Some(snapshot) = self.snapshot() {
+                    self.push_undo(snapshot);
+                }
+                self.graph().remove_selected();
+            }
+        }
+
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -541,23 +2503,121 @@ impl eframe::App for PixelLab {
                 let is_web = cfg!(target_arch = "wasm32");
                 if !is_web {
                     ui.menu_button("File", |ui| {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        {
+                            if ui.button("Open…").clicked() {
+                                ui.close_menu();
+                                self.open_file();
+                            }
+                            if ui.button("Save").clicked() {
+                                ui.close_menu();
+                                self.save_file();
+                            }
+                            if ui.button("Save As…").clicked() {
+                                ui.close_menu();
+                                self.save_file_as();
+                            }
+                            ui.separator();
+                        }
                         if ui.button("Quit").clicked() {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
                     });
                     ui.add_space(16.0);
                 }
+                ui.menu_button("Edit", |ui| {
+                    if ui.add_enabled(self.history.can_undo(), egui::Button::new("Undo")).clicked() {
+                        self.undo();
+                    }
+                    if ui.add_enabled(self.history.can_redo(), egui::Button::new("Redo")).clicked() {
+                        self.redo();
+                    }
+                    ui.separator();
+                    if ui.add_enabled(self.timeline.selected_index().is_some(), egui::Button::new("Copy graph as DOT")).clicked() {
+                        ui.close_menu();
+                        ctx.copy_text(graph_to_dot(self.graph()));
+                        self.status = "copied graph as dot".into();
+                    }
+                });
+                ui.add_space(16.0);
+                ui.toggle_value(&mut self.show_settings, "Settings");
+                ui.add_space(16.0);
 
                 egui::widgets::global_theme_preference_buttons(ui);
             });
         });
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
-            ui.toggle_value(&mut self.play, "play");
+            ui.horizontal(|ui| {
+                if ui.button("prev").clicked() {
+                    self.timeline.step_frames(-1);
+                }
+                ui.toggle_value(&mut self.play, "play");
+                if ui.button("next").clicked() {
+                    self.timeline.step_frames(1);
+                }
+                for playback in Playback::ALL {
+                    ui.selectable_value(&mut self.playback, playback, playback.name());
+                }
+                ui.add(egui::Slider::new(&mut self.speed, 0.25..=4.0).logarithmic(true).suffix("x"));
+                let elapsed = self.timeline.caret.duration_since(&Instant::zero());
+                ui.monospace(format!(
+                    "{} frame {}/{}",
+                    elapsed.format(self.timeline.fps),
+                    elapsed.as_frames(self.timeline.fps),
+                    self.timeline.frame_count(),
+                ));
+                if !self.status.is_empty() {
+                    ui.label(&self.status);
+                }
+                ui.separator();
+                if let Some(export) = &mut self.export {
+                    match export.step(&self.timeline, &self.video_settings) {
+                        Ok(()) => ctx.request_repaint(),
+                        Err(error) => {
+                            self.error_message = Some(format!("could not export frame {}: {}", export.next_frame, error));
+                            export.next_frame = export.frame_count;
+                        },
+                    }
+                    ui.add(egui::ProgressBar::new(export.progress()).show_percentage());
+                    if export.is_done() && self.error_message.is_none() {
+                        self.status = format!("exported {} frames", export.frame_count);
+                    }
+                    if export.is_done() || ui.button("cancel").clicked() {
+                        self.export = None;
+                    }
+                } else {
+                    ui.label("frames directory");
+                    ui.text_edit_singleline(&mut self.export_directory);
+                    if ui.button("render").clicked() {
+                        match Export::frames(self.export_directory.clone().into(), &self.timeline) {
+                            Ok(export) => self.export = Some(export),
+                            Err(error) => self.error_message = Some(format!("could not create {}: {}", self.export_directory, error)),
+                        }
+                    }
+                    #[cfg(all(feature = "gif", not(target_arch = "wasm32")))]
+                    if ui.button("export gif").clicked() {
+                        let path = self.file_dialog().add_filter("gif", &["gif"]).save_file();
+                        if let Some(path) = self.pick(path) {
+                            match Export::gif(&path, &self.timeline, &self.video_settings) {
+                                Ok(export) => self.export = Some(export),
+                                Err(error) => self.error_message = Some(format!("could not create {}: {}", path.display(), error)),
+                            }
+                        }
+                    }
+                }
+            });
             if self.play {
-                // simple play
-                self.timeline.caret.millis += 1000 / self.timeline.fps as u32;
+                // slow motion steps in between frames, fast motion repaints more often
+                let step = 1000.0 / self.timeline.fps * self.speed.min(1.0);
+                self.timeline.caret.millis += step.max(1.0) as u32;
+                if self.timeline.caret.millis >= self.timeline.duration().millis {
+                    match self.playback {
+                        Playback::Once => self.play = false,
+                        Playback::Loop => self.timeline.caret = Instant::zero(),
+                    }
+                }
                 self.timeline.cap_caret();
-                ctx.request_repaint_after_secs(1.0 / self.timeline.fps);
+                ctx.request_repaint_after_secs(1.0 / (self.timeline.fps * self.speed.max(1.0)));
             }
             ui.add(&mut self.timeline);
             egui::warn_if_debug_build(ui);
@@ -565,64 +2625,867 @@ impl eframe::App for PixelLab {
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Pixel Labs");
-            // node editor
-            let response = self.graph().show(ctx, ui);
-            response.context_menu(|ui| {
-                if ui.button("float").clicked() {
-                    self.add_node(NodeType::Float(1.0));
-                }
-                if ui.button("color").clicked() {
-                    self.add_node(NodeType::Color(Color32::GRAY));
-                }
-                if ui.button("lerp").clicked() {
-                    self.add_node(NodeType::Lerp);
-                }
-                if ui.button("cubic").clicked() {
-                    self.add_node(NodeType::Cubic(true));
-                }
-                if ui.button("rotate").clicked() {
-                    self.add_node(NodeType::Rotate);
-                }
-                if ui.button("scale").clicked() {
-                    self.add_node(NodeType::Scale);
-                }
-                if ui.button("revolution").clicked() {
-                    self.add_node(NodeType::Revolution);
+            // problems with the graph, and with evaluating it as of the last finished frame
+            if let Some(graph) = self.timeline.selected_index().map(|index| &self.timeline.blocks[index].content) {
+                let mut warnings = validate(graph);
+                for (node_index, message) in self.rendered.iter().flat_map(|(_, _, diagnostics, _)| diagnostics) {
+                    let warning = (Some(*node_index), message.clone());
+                    if !warnings.contains(&warning) {
+                        warnings.push(warning);
+                    }
                 }
-                if ui.button("time").clicked() {
-                    self.add_node(NodeType::Time);
+                if !warnings.is_empty() {
+                    egui::TopBottomPanel::bottom("warnings").show_inside(ui, |ui| {
+                        egui::CollapsingHeader::new(format!("warnings ({})", warnings.len())).show(ui, |ui| {
+                            for (node_index, message) in &warnings {
+                                let text = match node_index.and_then(|index| graph.nodes.get(index).map(|node| (index, node))) {
+                                    Some((index, node)) => format!("{} #{}: {}", node.widget.title(), index, message),
+                                    None => message.clone(),
+                                };
+                                ui.colored_label(ui.visuals().warn_fg_color, text);
+                            }
+                        });
+                    });
                 }
-                if ui.button("hex").clicked() {
-                    self.add_node(NodeType::Hex);
+            }
+            // node editor
+            // pins show the value flowing through them at the caret
+            let settings = self.video_settings.clone();
+            let t = self.timeline.block_at(&self.timeline.caret).map_or(0.0, |(_, t)| t);
+            let index = self.timeline.selected_index().unwrap();
+            let inspection = &mut self.inspection;
+            let graph = &mut self.timeline.blocks[index].content;
+            let (response, before) = graph.show(ctx, ui, |ui, graph, pin_id| inspect_pin(ui, graph, pin_id, t, &settings, inspection));
+            if let Some(graph) = before {
+                self.push_undo((index, graph));
+            }
+            // dropping image files creates pixmap nodes where they land
+            let dropped: Vec<PathBuf> = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect());
+            if !dropped.is_empty() {
+                let position = ctx.input(|i| i.pointer.hover_pos()).unwrap_or(response.rect.center());
+                if let Some(snapshot) = self.snapshot() {
+                    self.push_undo(snapshot);
                 }
-                if ui.button("pixmap").clicked() {
-                    self.add_node(NodeType::Pixmap(PathBuf::new()));
+                for (index, path) in dropped.into_iter().enumerate() {
+                    let node = NodeType::Pixmap(path, Sampling::Nearest, AddressMode::Transparent);
+                    self.graph().add_node_at(node, position + Vec2::splat(16.0 * index as f32));
                 }
-                if ui.button("transform color field").clicked() {
-                    self.add_node(NodeType::TransformColorField);
+            }
+            response.context_menu(|ui| {
+                ui.text_edit_singleline(&mut self.node_filter).request_focus();
+                let mut category = "";
+                for (node_category, title, create) in NODE_REGISTRY {
+                    if !fuzzy_match(&self.node_filter, title) {
+                        continue;
+                    }
+                    if *node_category != category {
+                        category = node_category;
+                        ui.separator();
+                        ui.weak(category);
+                    }
+                    if ui.button(*title).clicked() {
+                        self.add_node(create());
+                        self.node_filter.clear();
+                        ui.close_menu();
+                    }
                 }
             });
     
 
             // output window
-            // evaluate pixmap
-            // compute global time
-            let t = self.timeline.global_time();
-            // compute local time
-            let local_t = self.timeline.local_time();
-            if let PinValue::Pixmap(pixmap) = resolve(&self.graph(), 0, 0, local_t) {
+            // only evaluate the graph when something it depends on changed
+            let key = render_key(&self.timeline, &self.video_settings, self.shown_onion_skin());
+            if key != self.requested {
+                let job = RenderJob {
+                    key,
+                    timeline: self.timeline.clone(),
+                    settings: self.video_settings.clone(),
+                    onion_skin: self.shown_onion_skin(),
+                    // thumbnails keep their last image during playback
+                    thumbnails: !self.play,
+                };
+                self.renderer.request(job);
+                self.requested = key;
+            }
+            if let Some(((key, output, diagnostics, grid), thumbnails)) = self.renderer.latest() {
+                if let Some(index) = self.timeline.selected_index() {
+                    let nodes = &mut self.timeline.blocks[index].content.nodes;
+                    for (node_index, pixmap) in thumbnails {
+                        let Some(node) = nodes.get_mut(node_index) else { continue };
+                        let image = ColorImage::from_rgba_premultiplied([pixmap.width() as usize, pixmap.height() as usize], pixmap.data());
+                        match &mut node.thumbnail {
+                            Some(texture) => texture.set(image, TextureOptions::NEAREST),
+                            None => node.thumbnail = Some(ctx.load_texture(format!("thumbnail {}", node_index), image, TextureOptions::NEAREST)),
+                        }
+                    }
+                }
                 self.output_texture.set(
                     ColorImage::from_rgba_premultiplied(
-                        [pixmap.width() as usize, pixmap.height() as usize],
-                        pixmap.data(),
+                        [output.width() as usize, output.height() as usize],
+                        output.data(),
                     ),
-                    TextureOptions::default(),
+                    TextureOptions::NEAREST,
                 );
+                self.rendered = Some((key, output, diagnostics, grid));
+            }
+            // the most recent finished frame is shown until the next one is done
+            let (key, output, diagnostics, grid) = self.rendered.take()
+                .unwrap_or_else(|| (0, self.video_settings.empty_pixmap(), Vec::new(), None));
+
+            if let Some(message) = &self.error_message {
+                let mut dismissed = false;
+                egui::Window::new("Error").collapsible(false).resizable(false).show(ctx, |ui| {
+                    ui.label(message);
+                    dismissed = ui.button("OK").clicked();
+                });
+                if dismissed {
+                    self.error_message = None;
+                }
             }
 
+            egui::Window::new("Settings").open(&mut self.show_settings).show(ctx, |ui| {
+                egui::Grid::new("settings").show(ui, |ui| {
+                    // dimensions must be positive to allocate a pixmap
+                    ui.label("width");
+                    ui.add(egui::DragValue::new(&mut self.video_settings.resolution[0]).range(1..=4096));
+                    ui.end_row();
+                    ui.label("height");
+                    ui.add(egui::DragValue::new(&mut self.video_settings.resolution[1]).range(1..=4096));
+                    ui.end_row();
+                    ui.label("fps");
+                    ui.add(egui::DragValue::new(&mut self.timeline.fps).range(1.0..=120.0));
+                    ui.end_row();
+                    ui.label("anti-alias");
+                    ui.checkbox(&mut self.video_settings.anti_alias, "");
+                    ui.end_row();
+                });
+            });
+
+            #[cfg(not(target_arch = "wasm32"))]
+            let (mut save_frame, mut save_svg) = (false, false);
             egui::Window::new("Output").show(ctx, |ui| {
-                ui.add(egui::Image::from_texture(&self.output_texture));
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    if ui.button("save frame").clicked() {
+                        save_frame = true;
+                    }
+                    if ui.button("save svg").clicked() {
+                        save_svg = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.onion_skin.frames).range(0..=5).prefix("onion skin "));
+                    ui.add_enabled(self.onion_skin.frames > 0, egui::Slider::new(&mut self.onion_skin.opacity, 0.0..=1.0).text("opacity"));
+                });
+                #[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+                if ui.button("copy").clicked() {
+                    self.status = match copy_to_clipboard(&output) {
+                        Ok(()) => "copied frame to clipboard".into(),
+                        Err(error) => format!("could not copy frame: {}", error),
+                    };
+                }
+                // the output node reports what's wrong with its input
+                let output_node = self.timeline.selected_index().and_then(|index| output_index(&self.timeline.blocks[index].content));
+                if let Some((_, message)) = diagnostics.iter().find(|(node_index, _)| Some(*node_index) == output_node) {
+                    ui.colored_label(ui.visuals().error_fg_color, message);
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.checkerboard, "checkerboard");
+                    for zoom in PreviewZoom::ALL {
+                        ui.selectable_value(&mut self.zoom, zoom, zoom.name());
+                    }
+                });
+                let preview = |ui: &mut Ui| {
+                    let size = self.zoom.size(self.output_texture.size_vec2(), ui.available_size(), ctx.pixels_per_point());
+                    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+                    if self.checkerboard {
+                        paint_checkerboard(ui.painter_at(rect), rect);
+                    }
+                    egui::Image::from_texture(&self.output_texture).paint_at(ui, rect);
+                    // the worker hands over the grid of hex outputs, so picking doesn't evaluate anything
+                    if let (Some(grid), Some(position)) = (&grid, response.hover_pos()) {
+                        let pixel = (position - rect.min) / rect.size() * self.output_texture.size_vec2();
+                        if let Some((column, row)) = grid.hex_at(tiny_skia::Point::from_xy(pixel.x, pixel.y)) {
+                            response.on_hover_text_at_pointer(format!("hex {}, {}", column, row));
+                        }
+                    }
+                };
+                // magnified previews scroll, fit follows the window size
+                match self.zoom {
+                    PreviewZoom::Fit => preview(ui),
+                    PreviewZoom::Pixels(_) => {
+                        egui::ScrollArea::both().show(ui, preview);
+                    },
+                }
             });
+            #[cfg(not(target_arch = "wasm32"))]
+            if save_frame {
+                self.save_frame();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if save_svg {
+                self.save_svg();
+            }
+            self.rendered = Some((key, output, diagnostics, grid));
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeline_with(graph: Graph<NodeType>) -> Timeline<Graph<NodeType>> {
+        let mut timeline = Timeline::new(30.0);
+        timeline.blocks.push(Block::new(Duration::from_secs(1.0), graph));
+        timeline
+    }
+
+    // links an output pin of one node to an input pin of another
+    fn link(graph: &mut Graph<NodeType>, from: (usize, usize), to: (usize, usize)) {
+        let output = PinId { node_index: from.0, pin_index: from.1, direction: PinDirection::Output };
+        let input = PinId { node_index: to.0, pin_index: to.1, direction: PinDirection::Input };
+        graph.links.push((output, input));
+    }
+
+    // a small red rectangle, smaller than the output
+    fn small_frame_graph() -> Graph<NodeType> {
+        let mut graph = create_graph();
+        graph.add_node(NodeType::Color(Color32::RED));
+        graph.add_node(NodeType::Background);
+        graph.add_node(NodeType::Vec2(Vec2::new(10.0, 6.0)));
+        graph.add_node(NodeType::Crop);
+        link(&mut graph, (1, 0), (2, 1));
+        link(&mut graph, (2, 0), (4, 0));
+        link(&mut graph, (3, 0), (4, 2));
+        link(&mut graph, (4, 0), (0, 0));
+        graph
+    }
+
+    #[test]
+    fn undo_restores_the_graph_before_an_edit_and_redo_reapplies_it() {
+        let mut timeline = timeline_with(create_graph());
+        let mut history = History::default();
+        history.push((0, timeline.blocks[0].content.clone()));
+        timeline.blocks[0].content.add_node(NodeType::Time);
+        assert_eq!(timeline.blocks[0].content.nodes.len(), 2);
+
+        history.undo(&mut timeline);
+        assert_eq!(timeline.blocks[0].content.nodes.len(), 1);
+        assert!(!history.can_undo());
+        assert!(history.can_redo());
+
+        history.redo(&mut timeline);
+        assert_eq!(timeline.blocks[0].content.nodes.len(), 2);
+        assert!(history.can_undo());
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let mut timeline = timeline_with(create_graph());
+        let mut history = History::default();
+        history.push((0, timeline.blocks[0].content.clone()));
+        timeline.blocks[0].content.add_node(NodeType::Time);
+        history.undo(&mut timeline);
+        history.push((0, timeline.blocks[0].content.clone()));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undo_depth_is_limited() {
+        let mut timeline = timeline_with(create_graph());
+        let mut history = History::default();
+        for _ in 0..UNDO_DEPTH + 10 {
+            history.push((0, timeline.blocks[0].content.clone()));
+            timeline.blocks[0].content.add_node(NodeType::Time);
+        }
+        for _ in 0..UNDO_DEPTH + 10 {
+            history.undo(&mut timeline);
+        }
+        // the oldest edits can no longer be undone
+        assert_eq!(timeline.blocks[0].content.nodes.len(), 11);
+    }
+
+    #[test]
+    fn exported_frames_are_centered_at_the_settings_size() {
+        let timeline = timeline_with(small_frame_graph());
+        let settings = VideoSettings::default();
+        assert_eq!(render_frame(&timeline, 0, &settings).map(|pixmap| (pixmap.width(), pixmap.height())), Some((10, 6)));
+
+        let frame = export_frame(&timeline, 0, &settings);
+        assert_eq!((frame.width(), frame.height()), settings.size());
+        assert_eq!(frame.pixel(160, 100).unwrap().demultiply(), tiny_skia::ColorU8::from_rgba(255, 0, 0, 255));
+        assert_eq!(frame.pixel(0, 0).unwrap().alpha(), 0);
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn gif_frames_share_one_palette_and_the_screen_size() {
+        let timeline = timeline_with(small_frame_graph());
+        let settings = VideoSettings::default();
+        let path = std::env::temp_dir().join(format!("pixellab-export-{}.gif", std::process::id()));
+        let mut export = Export::gif(&path, &timeline, &settings).unwrap();
+        while !export.is_done() {
+            export.step(&timeline, &settings).unwrap();
+        }
+        drop(export);
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(std::fs::File::open(&path).unwrap()).unwrap();
+        assert!(decoder.global_palette().is_some());
+        assert_eq!((decoder.width() as u32, decoder.height() as u32), settings.size());
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert!(frame.palette.is_none());
+            assert_eq!((frame.width as u32, frame.height as u32), settings.size());
+            frames += 1;
+        }
+        assert_eq!(frames, timeline.frame_count());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn render_timeline_writes_numbered_frames_at_the_given_size() {
+        let mut timeline = timeline_with(small_frame_graph());
+        timeline.blocks[0].duration = Duration::from_millis(100);
+        let directory = std::env::temp_dir().join(format!("pixellab-render-{}", std::process::id()));
+        let input = directory.with_extension("json");
+        std::fs::write(&input, save_timeline(&timeline, &VideoSettings::default()).unwrap().dump()).unwrap();
+
+        let frame_count = render_timeline(&input, directory.clone(), Some(20.0), Some([16, 8])).unwrap();
+        assert_eq!(frame_count, 2);
+        let mut names: Vec<_> = std::fs::read_dir(&directory).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["frame_0000.png", "frame_0001.png"]);
+        let frame = Pixmap::load_png(directory.join("frame_0001.png")).unwrap();
+        assert_eq!((frame.width(), frame.height()), (16, 8));
+
+        assert!(render_timeline(&input, directory.clone(), None, Some([0, 8])).is_err());
+
+        // without flags the file settings are used
+        std::fs::remove_dir_all(&directory).unwrap();
+        let settings = VideoSettings { resolution: [12, 4], anti_alias: true };
+        std::fs::write(&input, save_timeline(&timeline, &settings).unwrap().dump()).unwrap();
+        assert_eq!(render_timeline(&input, directory.clone(), None, None).unwrap(), 3);
+        let frame = Pixmap::load_png(directory.join("frame_0000.png")).unwrap();
+        assert_eq!((frame.width(), frame.height()), (12, 4));
+        std::fs::remove_dir_all(&directory).unwrap();
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn timeline_json_keeps_the_video_settings() {
+        let mut timeline = timeline_with(small_frame_graph());
+        timeline.fps = 12.0;
+        let settings = VideoSettings { resolution: [64, 48], anti_alias: false };
+        let raw = save_timeline(&timeline, &settings).unwrap().dump();
+
+        let (loaded, loaded_settings) = load_timeline(&raw).unwrap();
+        assert_eq!(loaded.fps, 12.0);
+        assert_eq!(loaded.blocks.len(), 1);
+        assert_eq!(loaded_settings.resolution, [64, 48]);
+        assert!(!loaded_settings.anti_alias);
+    }
+
+    #[test]
+    fn older_timeline_json_gets_the_default_settings() {
+        let timeline = timeline_with(small_frame_graph());
+        let settings = VideoSettings { resolution: [64, 48], anti_alias: false };
+        let blocks = save_timeline(&timeline, &settings).unwrap()["blocks"].dump();
+
+        let (loaded, loaded_settings) = load_timeline(&blocks).unwrap();
+        assert_eq!(loaded.fps, 30.0);
+        assert_eq!(loaded.blocks[0].content.links.len(), 4);
+        assert_eq!(loaded_settings.resolution, VideoSettings::default().resolution);
+        assert!(loaded_settings.anti_alias);
+    }
+
+    #[test]
+    fn stepping_frames_lands_on_every_frame_and_back() {
+        for fps in [24.0, 29.97, 30.0, 60.0] {
+            let mut timeline = timeline_with(create_graph());
+            timeline.fps = fps;
+            let frame = |timeline: &Timeline<Graph<NodeType>>| timeline.caret.duration_since(&Instant::zero()).as_frames(fps);
+            for expected in 1..timeline.frame_count() {
+                timeline.step_frames(1);
+                assert_eq!(frame(&timeline), expected, "{} fps", fps);
+            }
+            timeline.step_frames(5);
+            assert_eq!(frame(&timeline), timeline.frame_count() - 1, "{} fps", fps);
+            timeline.step_frames(-(timeline.frame_count() as i32 - 1));
+            assert_eq!(timeline.caret.millis, 0, "{} fps", fps);
+            timeline.step_frames(-1);
+            assert_eq!(timeline.caret.millis, 0, "{} fps", fps);
+        }
+    }
+
+    #[test]
+    fn render_key_follows_the_blocks_that_can_show() {
+        let mut timeline = timeline_with(small_frame_graph());
+        timeline.blocks.push(Block::new(Duration::from_secs(1.0), small_frame_graph()));
+        let settings = VideoSettings::default();
+        let key = |timeline: &Timeline<Graph<NodeType>>, onion_skin| render_key(timeline, &settings, onion_skin);
+        let initial = key(&timeline, None);
+        assert_eq!(key(&timeline, None), initial);
+
+        // moving a node does not change the frame
+        timeline.blocks[0].content.nodes[1].position.x += 10.0;
+        assert_eq!(key(&timeline, None), initial);
+
+        // editing the showing block does
+        timeline.blocks[0].content.nodes[1].widget = NodeType::Color(Color32::BLUE);
+        let edited = key(&timeline, None);
+        assert_ne!(edited, initial);
+
+        // the next block only matters once ghosts reach into it
+        timeline.caret = Instant { millis: 990 };
+        let before = key(&timeline, None);
+        timeline.blocks[1].content.nodes[1].widget = NodeType::Color(Color32::GREEN);
+        assert_eq!(key(&timeline, None), before);
+        let skin = Some(OnionSkin { frames: 2, opacity: 0.5 });
+        let with_skin = key(&timeline, skin);
+        timeline.blocks[1].content.nodes[1].widget = NodeType::Color(Color32::RED);
+        assert_ne!(key(&timeline, skin), with_skin);
+
+        // as do the block lengths and the caret
+        let before = key(&timeline, None);
+        timeline.blocks[1].duration = Duration::from_secs(2.0);
+        assert_ne!(key(&timeline, None), before);
+        let before = key(&timeline, None);
+        timeline.caret = Instant { millis: 500 };
+        assert_ne!(key(&timeline, None), before);
+    }
+
+    #[test]
+    fn inspected_pins_are_evaluated_once_until_the_graph_changes() {
+        let mut graph = small_frame_graph();
+        let settings = VideoSettings::default();
+        // the crop output feeding the output node
+        let pin_id = PinId { node_index: 0, pin_index: 0, direction: PinDirection::Input };
+        let ctx = egui::Context::default();
+        let mut cache = None;
+        let mut inspect = |graph: &Graph<NodeType>, t: f32| {
+            let _ = ctx.run(egui::RawInput::default(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| inspect_pin(ui, graph, pin_id, t, &settings, &mut cache));
+            });
+            cache.as_ref().and_then(|inspection: &Inspection| inspection.texture.as_ref().map(TextureHandle::id))
+        };
+        let first = inspect(&graph, 0.0);
+        assert!(first.is_some());
+        assert_eq!(inspect(&graph, 0.0), first);
+        let later = inspect(&graph, 0.5);
+        assert_ne!(later, first);
+
+        graph.nodes[3].widget = NodeType::Vec2(Vec2::new(4.0, 4.0));
+        let edited = inspect(&graph, 0.5);
+        assert!(edited.is_some());
+        assert_ne!(edited, later);
+    }
+
+    #[test]
+    fn empty_and_zero_length_timelines_keep_the_caret_at_zero() {
+        let mut timeline: Timeline<Graph<NodeType>> = Timeline::new(30.0);
+        timeline.caret = Instant { millis: 500 };
+        timeline.cap_caret();
+        assert_eq!(timeline.caret.millis, 0);
+        assert_eq!(timeline.frame_count(), 0);
+        assert!(timeline.selected_index().is_none());
+        assert!(timeline.block_at(&timeline.caret).is_none());
+        timeline.step_frames(3);
+        assert_eq!(timeline.caret.millis, 0);
+        assert!(render_frame(&timeline, 0, &VideoSettings::default()).is_none());
+
+        // a block without length is never showing
+        timeline.blocks.push(Block::new(Duration::from_millis(0), create_graph()));
+        timeline.step_frames(-3);
+        assert_eq!(timeline.caret.millis, 0);
+        assert!(timeline.selected_index().is_none());
+        timeline.delete_selected();
+        assert_eq!(timeline.blocks.len(), 1);
+    }
+
+    #[test]
+    fn the_caret_stays_on_the_last_millisecond() {
+        let mut timeline = timeline_with(create_graph());
+        timeline.caret = Instant { millis: 5000 };
+        timeline.cap_caret();
+        assert_eq!(timeline.caret.millis, 999);
+        assert_eq!(timeline.selected_index(), Some(0));
+        timeline.delete_selected();
+        assert_eq!(timeline.caret.millis, 0);
+    }
+
+    // loads a timeline of one block holding the given graph json
+    fn load_graph_json(graph: &str) -> Result<Timeline<Graph<NodeType>>, String> {
+        let raw = format!(r#"{{"blocks": [{{"duration": 1000, "graph": {}}}]}}"#, graph);
+        load_timeline(&raw).map(|(timeline, _)| timeline).map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn malformed_timelines_fail_with_a_message() {
+        assert!(load_timeline("{\"blocks\": [").is_err());
+        let error = load_timeline(r#"{"blocks": 3}"#).err().unwrap().to_string();
+        assert!(error.contains("array of blocks"), "{}", error);
+        let error = load_timeline("42").err().unwrap().to_string();
+        assert!(error.contains("array of blocks"), "{}", error);
+
+        let error = load_graph_json(r#"{"nodes": [{"value": 1.0}]}"#).err().unwrap();
+        assert!(error.contains("node without a type"), "{}", error);
+        let error = load_graph_json(r#"{"nodes": [{"type": "teleport"}]}"#).err().unwrap();
+        assert!(error.contains("unknown node type \"teleport\""), "{}", error);
+        let error = load_graph_json(r#"{"nodes": [{"type": "float", "value": "one"}]}"#).err().unwrap();
+        assert!(error.contains("bad value for float node"), "{}", error);
+    }
+
+    #[test]
+    fn links_to_missing_nodes_or_pins_are_dropped_on_load() {
+        let timeline = load_graph_json(r#"{
+            "nodes": [{"type": "output"}, {"type": "time"}],
+            "links": [
+                {"from": {"node": 1, "pin": 0}, "to": {"node": 0, "pin": 0}},
+                {"from": {"node": 7, "pin": 0}, "to": {"node": 0, "pin": 0}},
+                {"from": {"node": 1, "pin": 0}, "to": {"node": 9, "pin": 0}},
+                {"from": {"node": 1, "pin": 3}, "to": {"node": 0, "pin": 0}},
+                {"from": {"node": 1, "pin": 0}, "to": {"node": 0, "pin": 1}},
+                {"from": {"node": 1}, "to": {"node": 0, "pin": 0}},
+                "not a link"
+            ]
+        }"#).unwrap();
+        let graph = &timeline.blocks[0].content;
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.links.len(), 1);
+        assert_eq!((graph.links[0].0.node_index, graph.links[0].1.node_index), (1, 0));
+    }
+
+    // evaluates the first output of a single node from its input values
+    fn evaluate(node: NodeType, inputs: Vec<PinValue>) -> PinValue {
+        node.evaluate(inputs, 0, 0.0, &VideoSettings::default(), &mut Vec::new())
+    }
+
+    #[test]
+    fn remap_node_defaults_to_the_unit_ranges() {
+        assert_eq!(evaluate(NodeType::Remap, vec![PinValue::Float(0.25)]).f32(), Some(0.25));
+        let inputs = vec![PinValue::Float(5.0), PinValue::Float(0.0), PinValue::Float(10.0), PinValue::Float(-1.0), PinValue::Float(1.0)];
+        assert_eq!(evaluate(NodeType::Remap, inputs).f32(), Some(0.0));
+    }
+
+    #[test]
+    fn path_node_follows_and_faces_along_the_curve() {
+        let inputs = |t: f32| {
+            let points = [(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0)];
+            std::iter::once(PinValue::Float(t)).chain(points.map(|(x, y)| PinValue::Vec2(Vec2::new(x, y)))).collect()
+        };
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+        let middle = evaluate(NodeType::Path(true, false), inputs(0.5)).transform().unwrap();
+        assert!(close(middle.tx, 5.0) && close(middle.ty, 7.5), "{:?}", middle);
+        assert!(close(middle.sx, 1.0) && close(middle.ky, 0.0), "{:?}", middle);
+
+        // heading straight down the first control arm
+        let start = evaluate(NodeType::Path(true, false), inputs(0.0)).transform().unwrap();
+        assert!(close(start.sx, 0.0) && close(start.ky, 1.0), "{:?}", start);
+        let unoriented = evaluate(NodeType::Path(false, false), inputs(0.0)).transform().unwrap();
+        assert!(unoriented.is_identity(), "{:?}", unoriented);
+
+        // t is clamped to the curve
+        let past = evaluate(NodeType::Path(false, true), inputs(3.0)).transform().unwrap();
+        assert!(close(past.tx, 10.0) && close(past.ty, 0.0), "{:?}", past);
+
+        // the second output measures the curve, longer than the chord and shorter than the control polygon
+        let length = NodeType::Path(true, true).evaluate(inputs(0.0), 1, 0.0, &VideoSettings::default(), &mut Vec::new()).f32().unwrap();
+        assert!(length > 10.0 && length < 30.0, "{}", length);
+    }
+
+    #[test]
+    fn color_node_keeps_alpha_unpremultiplied() {
+        let translucent = Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+        let color = evaluate(NodeType::Color(translucent), vec![]).color().unwrap();
+        assert_eq!(color.to_color_u8(), tiny_skia::ColorU8::from_rgba(255, 0, 0, 128));
+
+        // the alpha survives saving
+        let raw = from_nodetype(NodeType::Color(translucent));
+        assert_eq!(raw["value"], "#ff000080");
+        assert!(matches!(into_node(&raw), Ok(NodeType::Color(value)) if value == translucent));
+    }
+
+    #[test]
+    fn parse_color_reads_hex_and_reports_bad_input() {
+        let parse = |text: &str| {
+            let mut errors = Vec::new();
+            let value = NodeType::ParseColor.evaluate(vec![PinValue::String(text.into())], 0, 0.0, &VideoSettings::default(), &mut errors);
+            (value.color().unwrap().to_color_u8(), errors)
+        };
+        assert_eq!(parse("#336699"), (tiny_skia::ColorU8::from_rgba(0x33, 0x66, 0x99, 255), vec![]));
+        assert_eq!(parse(" #33669980 ").0, tiny_skia::ColorU8::from_rgba(0x33, 0x66, 0x99, 0x80));
+
+        let (color, errors) = parse("teal");
+        assert_eq!(color.alpha(), 0);
+        assert_eq!(errors, ["\"teal\" is not a hex color"]);
+        // an empty string is not worth an error
+        assert!(parse("").1.is_empty());
+    }
+
+    #[test]
+    fn combining_split_channels_gives_back_the_color() {
+        let color = Color::from_rgba(0.1, 0.4, 0.7, 0.6).unwrap();
+        let channels = (0..4)
+            .map(|pin| NodeType::SplitRGBA.evaluate(vec![PinValue::Color(color)], pin, 0.0, &VideoSettings::default(), &mut Vec::new()))
+            .collect();
+        assert_eq!(evaluate(NodeType::CombineRGBA, channels).color(), Some(color));
+        // unconnected channels are black and opaque
+        assert_eq!(evaluate(NodeType::CombineRGBA, vec![]).color(), Some(Color::BLACK));
+    }
+
+    #[test]
+    fn links_select_outputs_by_pin_index() {
+        // swap red and blue by crossing the links between split and combine
+        let mut graph = create_graph();
+        graph.add_node(NodeType::Color(Color32::from_rgb(255, 128, 0)));
+        graph.add_node(NodeType::SplitRGBA);
+        graph.add_node(NodeType::CombineRGBA);
+        link(&mut graph, (1, 0), (2, 0));
+        for (from, to) in [(0, 2), (1, 1), (2, 0), (3, 3)] {
+            link(&mut graph, (2, from), (3, to));
+        }
+        let color = resolve(&graph, 3, 0, 0.0, &VideoSettings::default(), &mut Vec::new()).color().unwrap();
+        assert_eq!(color.to_color_u8(), tiny_skia::ColorU8::from_rgba(0, 128, 255, 255));
+    }
+
+    #[test]
+    fn spline_outputs_position_and_velocity() {
+        let inputs = || vec![PinValue::Float(0.5), PinValue::Vec2(Vec2::new(0.0, 0.0)), PinValue::Vec2(Vec2::new(8.0, 0.0))];
+        let evaluate_pin = |pin_index| NodeType::Spline(2).evaluate(inputs(), pin_index, 0.0, &VideoSettings::default(), &mut Vec::new());
+        assert_eq!(evaluate_pin(0).vec2(), Some(Vec2::new(4.0, 0.0)));
+        assert_eq!(evaluate_pin(1).vec2(), Some(Vec2::new(10.0, 0.0)));
+    }
+
+    #[test]
+    fn step_snaps_down_to_multiples() {
+        let step = |value: f32, step: f32| evaluate(NodeType::Step, vec![PinValue::Float(value), PinValue::Float(step)]).f32().unwrap();
+        assert_eq!(step(0.7, 0.25), 0.5);
+        assert_eq!(step(1.0, 0.25), 1.0);
+        assert_eq!(step(-0.1, 0.25), -0.25);
+        // a zero step leaves the value alone
+        assert_eq!(step(0.7, 0.0), 0.7);
+    }
+
+    #[test]
+    fn abs_and_sign() {
+        let apply = |node: NodeType, value: f32| evaluate(node, vec![PinValue::Float(value)]).f32().unwrap();
+        assert_eq!(apply(NodeType::Abs, -2.5), 2.5);
+        assert_eq!(apply(NodeType::Abs, 3.0), 3.0);
+        assert_eq!(apply(NodeType::Sign, -2.5), -1.0);
+        assert_eq!(apply(NodeType::Sign, 0.1), 1.0);
+        assert_eq!(apply(NodeType::Sign, 0.0), 0.0);
+        assert_eq!(apply(NodeType::Sign, -0.0), 0.0);
+    }
+
+    #[test]
+    fn switch_picks_an_input_by_selector() {
+        let switch = |selector: f32| {
+            let inputs = vec![PinValue::Float(selector), PinValue::String("a".into()), PinValue::String("b".into())];
+            evaluate(NodeType::Switch, inputs).string().unwrap()
+        };
+        assert_eq!(switch(0.0), "a");
+        assert_eq!(switch(0.49), "a");
+        assert_eq!(switch(0.5), "b");
+        assert_eq!(switch(7.0), "b");
+        // an unconnected selector picks the first input
+        assert_eq!(evaluate(NodeType::Switch, vec![PinValue::None, PinValue::Float(1.0)]).f32(), Some(1.0));
+    }
+
+    #[test]
+    fn skew_shears_along_each_axis() {
+        let skew = |kx: f32, ky: f32| evaluate(NodeType::Skew, vec![PinValue::Float(kx), PinValue::Float(ky)]).transform().unwrap();
+        let mut point = [tiny_skia::Point::from_xy(2.0, 3.0)];
+        skew(0.5, 0.0).map_points(&mut point);
+        assert_eq!((point[0].x, point[0].y), (3.5, 3.0));
+        let mut point = [tiny_skia::Point::from_xy(2.0, 3.0)];
+        skew(0.0, -1.0).map_points(&mut point);
+        assert_eq!((point[0].x, point[0].y), (2.0, 1.0));
+        assert!(evaluate(NodeType::Skew, vec![]).transform().unwrap().is_identity());
+    }
+
+    #[test]
+    fn fit_modes_map_the_output_corner_into_the_image() {
+        // a wide image on a square output, both centered
+        let corner = |mode: FitMode| {
+            let mut point = [tiny_skia::Point::from_xy(25.0, 25.0)];
+            mode.transform((100, 50), (50, 50)).map_points(&mut point);
+            (point[0].x, point[0].y)
+        };
+        // the image edge reaches the output edge horizontally, leaving bars above and below
+        assert_eq!(corner(FitMode::Contain), (50.0, 50.0));
+        // the image edge reaches the output edge vertically, cutting off the sides
+        assert_eq!(corner(FitMode::Cover), (25.0, 25.0));
+        assert_eq!(corner(FitMode::Stretch), (50.0, 25.0));
+    }
+
+    #[test]
+    fn validation_reports_structural_problems() {
+        let mut graph = create_graph();
+        graph.add_node(NodeType::Pixelate);
+        graph.add_node(NodeType::Output);
+        graph.add_node(NodeType::Float(1.0));
+        link(&mut graph, (3, 0), (1, 1));
+        let warnings = validate(&graph);
+        let expected = [
+            (Some(2), "only the first output node is rendered"),
+            (Some(0), "nothing is connected to the output"),
+            (Some(1), "input pixmap is not connected"),
+            (Some(1), "the output goes nowhere"),
+            (Some(2), "nothing is connected to the output"),
+        ];
+        assert_eq!(warnings, expected.map(|(index, message)| (index, message.to_string())));
+
+        let mut graph = create_graph();
+        graph.add_node(NodeType::Color(Color32::RED));
+        graph.add_node(NodeType::Rasterize);
+        link(&mut graph, (1, 0), (2, 0));
+        link(&mut graph, (2, 0), (0, 0));
+        assert_eq!(validate(&graph), []);
+        graph.nodes[0].widget = NodeType::Comment(String::new());
+        assert!(validate(&graph).contains(&(None, "there is no output node".to_string())));
+    }
+
+    #[test]
+    fn dot_output_lists_nodes_and_labeled_links() {
+        let graph = small_frame_graph();
+        let dot = graph_to_dot(&graph);
+        assert!(dot.starts_with("digraph {\n") && dot.ends_with("}\n"), "{}", dot);
+        for (index, node) in graph.nodes.iter().enumerate() {
+            assert!(dot.contains(&format!("    n{} [label=\"{}\"];", index, node.widget.title())), "{}", dot);
+        }
+        assert!(dot.contains("    n1 -> n2 [label=\"0:1\"];"), "{}", dot);
+        assert!(dot.contains("    n4 -> n0 [label=\"0:0\"];"), "{}", dot);
+        assert_eq!(dot.matches("->").count(), graph.links.len());
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn json_and_ron_round_trips_agree() {
+        // one of every node type, with a link into every node that has inputs
+        let mut graph = create_graph();
+        for (_, _, constructor) in NODE_REGISTRY {
+            graph.add_node(constructor());
+        }
+        graph.nodes[3].position = Pos2::new(12.0, -40.0);
+        graph.nodes[5].collapsed = true;
+        for to in 0..graph.nodes.len() {
+            let inputs = graph.nodes[to].widget.in_pins();
+            let from = (1..graph.nodes.len()).find(|from| {
+                let outputs = graph.nodes[*from].widget.out_pins();
+                *from != to && inputs.first().is_some_and(|input| outputs.first().is_some_and(|output| input.pin_type.accepts(&output.pin_type)))
+            });
+            if let Some(from) = from {
+                link(&mut graph, (from, 0), (to, 0));
+            }
+        }
+        let pins: Vec<PinType> = graph.nodes.iter().flat_map(|node| node.widget.in_pins().into_iter().chain(node.widget.out_pins())).map(|pin| pin.pin_type).collect();
+        for pin_type in [PinType::Float, PinType::Vec2, PinType::String, PinType::Color, PinType::Transform, PinType::Pixmap, PinType::ColorField, PinType::Palette, PinType::Any] {
+            assert!(pins.contains(&pin_type), "{:?}", pin_type);
+        }
+        let settings = VideoSettings { resolution: [64, 48], anti_alias: false };
+
+        let json = write_timeline(std::path::Path::new("a.json"), &timeline_with(graph), &settings).unwrap();
+        let (timeline, settings) = read_timeline(std::path::Path::new("a.json"), &json).unwrap();
+        assert!(timeline.blocks[0].content.links.len() > 20);
+        let ron = write_timeline(std::path::Path::new("a.ron"), &timeline, &settings).unwrap();
+        let (timeline, settings) = read_timeline(std::path::Path::new("a.ron"), &ron).unwrap();
+        assert_eq!(write_timeline(std::path::Path::new("a.json"), &timeline, &settings).unwrap(), json);
+    }
+
+    #[test]
+    fn crop_node_rejects_empty_and_huge_sizes() {
+        let crop = |size: Option<Vec2>| {
+            let mut pixmap = Pixmap::new(8, 6).unwrap();
+            pixmap.fill(Color::WHITE);
+            let inputs = vec![PinValue::Pixmap(pixmap), PinValue::Vec2(Vec2::new(2.0, 1.0)), size.map_or(PinValue::None, PinValue::Vec2)];
+            let mut errors = Vec::new();
+            let value = NodeType::Crop.evaluate(inputs, 0, 0.0, &VideoSettings::default(), &mut errors);
+            (value.pixmap().map(|pixmap| (pixmap.width(), pixmap.height())), errors)
+        };
+        // the rest of the image by default
+        assert_eq!(crop(None), (Some((6, 5)), vec![]));
+        assert_eq!(crop(Some(Vec2::new(4096.0, 1.0))).0, Some((4096, 1)));
+        assert_eq!(crop(Some(Vec2::new(0.0, 3.0))), (None, vec!["crop size 0x3 is outside 1x1 to 4096x4096".to_string()]));
+        assert_eq!(crop(Some(Vec2::new(3.0, 4097.0))).0, None);
+        assert_eq!(crop(Some(Vec2::new(-2.0, 3.0))).0, None);
+    }
+
+    #[test]
+    fn zero_length_blocks_load_as_one_millisecond() {
+        let raw = r#"{"blocks": [{"duration": 0, "graph": {"nodes": [{"type": "output"}]}}]}"#;
+        let (timeline, _) = read_timeline(std::path::Path::new("zero.json"), raw).unwrap();
+        assert_eq!(timeline.blocks[0].duration.millis, 1);
+        assert_eq!(timeline.selected_index(), Some(0));
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn zero_length_blocks_load_from_ron_as_one_millisecond() {
+        let mut timeline = timeline_with(create_graph());
+        timeline.blocks[0].duration = Duration::from_millis(0);
+        let ron = write_timeline(std::path::Path::new("zero.ron"), &timeline, &VideoSettings::default()).unwrap();
+        let (timeline, _) = read_timeline(std::path::Path::new("zero.ron"), &ron).unwrap();
+        assert_eq!(timeline.blocks[0].duration.millis, 1);
+        assert_eq!(timeline.selected_index(), Some(0));
+    }
+
+    #[test]
+    fn hex_outputs_hand_over_their_grid_for_picking() {
+        let mut graph = create_graph();
+        graph.add_node(NodeType::Hex(Orientation::PointyTop, true));
+        link(&mut graph, (1, 0), (0, 0));
+        let mut timeline = timeline_with(graph);
+        let settings = VideoSettings::default();
+        let (width, height) = settings.size();
+        let grid = output_hex_grid(&timeline, &timeline.caret, &settings).unwrap();
+        // the grid is centered on the frame
+        let center = tiny_skia::Point::from_xy(0.5 * width as f32, 0.5 * height as f32);
+        assert_eq!(grid.hex_at(center), Some((0, 0)));
+        // default spacing of 8 puts the next hex in the row about 14 pixels over
+        assert_eq!(grid.hex_at(tiny_skia::Point::from_xy(center.x + 14.0, center.y)), Some((1, 0)));
+
+        timeline.blocks[0].content.nodes[1].muted = true;
+        assert!(output_hex_grid(&timeline, &timeline.caret, &settings).is_none());
+        let timeline = timeline_with(small_frame_graph());
+        assert!(output_hex_grid(&timeline, &timeline.caret, &settings).is_none());
+    }
+
+    #[test]
+    fn a_muted_grayscale_node_leaves_colors_unchanged() {
+        // color -> rasterize -> grayscale -> output
+        let mut graph = create_graph();
+        graph.add_node(NodeType::Color(Color32::from_rgb(200, 40, 90)));
+        graph.add_node(NodeType::Rasterize);
+        graph.add_node(NodeType::Grayscale);
+        link(&mut graph, (1, 0), (2, 0));
+        link(&mut graph, (2, 0), (3, 0));
+        link(&mut graph, (3, 0), (0, 0));
+        let mut timeline = timeline_with(graph);
+        let settings = VideoSettings::default();
+        let input = resolve(&timeline.blocks[0].content, 2, 0, 0.0, &settings, &mut Vec::new()).pixmap().unwrap();
+
+        let gray = render_frame(&timeline, 0, &settings).unwrap();
+        let pixel = gray.pixel(0, 0).unwrap();
+        assert!(pixel.red() == pixel.green() && pixel.green() == pixel.blue());
+        assert_ne!(gray.data(), input.data());
+
+        timeline.blocks[0].content.nodes[3].muted = true;
+        assert_eq!(render_frame(&timeline, 0, &settings).unwrap().data(), input.data());
+    }
+
+    #[test]
+    fn muted_flags_survive_saving() {
+        let mut graph = small_frame_graph();
+        graph.nodes[2].muted = true;
+        let loaded = load_graph(&save_graph(&graph).unwrap()).unwrap();
+        let muted: Vec<bool> = loaded.nodes.iter().map(|node| node.muted).collect();
+        assert_eq!(muted, [false, false, true, false, false]);
+    }
+}
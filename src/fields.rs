@@ -1,8 +1,14 @@
-use tiny_skia::Point;
+use tiny_skia::{Color, IntRect, Pixmap, Point, PremultipliedColorU8};
+
+use crate::tweening::smoothstep;
 
 // represnts a field that can be evaluated a specific point, e.g. color field, scalar field, vector field
 pub(crate) trait Field2<T> {
     fn at(&self, position: Point) -> T;
+    // the value at every position, for fields known not to vary, so callers can skip sampling
+    fn constant(&self) -> Option<T> {
+        None
+    }
 }
 
 pub(crate) struct ConstantField<T: Clone> {
@@ -17,4 +23,579 @@ impl<T: Clone> Field2<T> for ConstantField<T> {
     fn at(&self, _position: Point) -> T {
         self.value.clone()
     }
+    fn constant(&self) -> Option<T> {
+        Some(self.value.clone())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Sampling {
+    Nearest,
+    Bilinear,
+}
+impl Sampling {
+    pub const ALL: [Sampling; 2] = [Sampling::Nearest, Sampling::Bilinear];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sampling::Nearest => "nearest",
+            Sampling::Bilinear => "bilinear",
+        }
+    }
+}
+
+// how texels outside the pixmap are addressed
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum AddressMode {
+    Transparent,
+    Clamp,
+    Wrap,
+    Mirror,
+}
+impl AddressMode {
+    pub const ALL: [AddressMode; 4] = [AddressMode::Transparent, AddressMode::Clamp, AddressMode::Wrap, AddressMode::Mirror];
+    pub fn name(&self) -> &'static str {
+        match self {
+            AddressMode::Transparent => "transparent",
+            AddressMode::Clamp => "clamp",
+            AddressMode::Wrap => "wrap",
+            AddressMode::Mirror => "mirror",
+        }
+    }
+    // maps a texel coordinate into 0..size, or None if it should be transparent
+    fn remap(&self, i: i32, size: i32) -> Option<i32> {
+        match self {
+            AddressMode::Transparent => (0..size).contains(&i).then_some(i),
+            AddressMode::Clamp => Some(i.clamp(0, size - 1)),
+            AddressMode::Wrap => Some(i.rem_euclid(size)),
+            AddressMode::Mirror => {
+                // ping-pong over a period of twice the size
+                let m = i.rem_euclid(2 * size);
+                Some(if m < size { m } else { 2 * size - 1 - m })
+            },
+        }
+    }
+}
+
+// samples a pixmap centered around the origin with a selectable filter and addressing
+pub(crate) struct SampledField {
+    pixmap: Pixmap,
+    sampling: Sampling,
+    address_mode: AddressMode,
+}
+impl SampledField {
+    pub fn new(pixmap: Pixmap, sampling: Sampling, address_mode: AddressMode) -> Self {
+        Self { pixmap, sampling, address_mode }
+    }
+    // premultiplied texel as floats
+    fn texel(&self, x: i32, y: i32) -> [f32; 4] {
+        let x = self.address_mode.remap(x, self.pixmap.width() as i32);
+        let y = self.address_mode.remap(y, self.pixmap.height() as i32);
+        let pixel = match (x, y) {
+            (Some(x), Some(y)) => self.pixmap.pixel(x as u32, y as u32),
+            _ => None,
+        };
+        let pixel = pixel.unwrap_or(PremultipliedColorU8::TRANSPARENT);
+        [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()].map(|channel| channel as f32 / 255.0)
+    }
+}
+impl Field2<Color> for SampledField {
+    fn at(&self, position: Point) -> Color {
+        let x = position.x + 0.5 * self.pixmap.width() as f32;
+        let y = position.y + 0.5 * self.pixmap.height() as f32;
+        let [r, g, b, a] = match self.sampling {
+            Sampling::Nearest => self.texel(x.floor() as i32, y.floor() as i32),
+            Sampling::Bilinear => {
+                // interpolate between the four closest texel centers
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+                let top = lerp4(self.texel(x0, y0), self.texel(x0 + 1, y0), tx);
+                let bottom = lerp4(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), tx);
+                lerp4(top, bottom, ty)
+            },
+        };
+        if a <= 0.0 {
+            return Color::TRANSPARENT;
+        }
+        let demultiply = |channel: f32| (channel / a).min(1.0);
+        Color::from_rgba(demultiply(r), demultiply(g), demultiply(b), a.min(1.0)).unwrap_or(Color::TRANSPARENT)
+    }
+}
+
+fn lerp4(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|i| a[i] * (1.0 - t) + b[i] * t)
+}
+
+// kaleidoscope: folds the angle around the origin into one of repeat sectors, twisting it further out
+pub(crate) struct PolarField {
+    field: Box<dyn Field2<Color>>,
+    repeat: f32,
+    twist: f32,
+}
+impl PolarField {
+    pub fn new(field: Box<dyn Field2<Color>>, repeat: f32, twist: f32) -> Self {
+        Self { field, repeat: repeat.max(1.0), twist }
+    }
+}
+impl Field2<Color> for PolarField {
+    fn at(&self, position: Point) -> Color {
+        let radius = position.x.hypot(position.y);
+        let sector = std::f32::consts::TAU / self.repeat;
+        let angle = position.y.atan2(position.x).rem_euclid(sector) + self.twist * radius;
+        self.field.at(Point::from_xy(radius * angle.cos(), radius * angle.sin()))
+    }
+}
+
+// samples a field at the pixel centers of a new pixmap, centered around the origin
+pub(crate) fn rasterize(field: &dyn Field2<Color>, width: u32, height: u32) -> Option<Pixmap> {
+    rasterize_tile(field, width, height, IntRect::from_xywh(0, 0, width, height)?)
+}
+
+// edge length of the tiles used by rasterize_tiled
+pub(crate) const TILE_SIZE: u32 = 64;
+
+// samples the part of a width x height rasterization covered by the tile
+pub(crate) fn rasterize_tile(field: &dyn Field2<Color>, width: u32, height: u32, tile: IntRect) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(tile.width(), tile.height())?;
+    for (index, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+        let x = tile.x() as u32 + index as u32 % tile.width();
+        let y = tile.y() as u32 + index as u32 / tile.width();
+        let position = Point::from_xy(x as f32 + 0.5 - 0.5 * width as f32, y as f32 + 0.5 - 0.5 * height as f32);
+        *pixel = field.at(position).premultiply().to_color_u8();
+    }
+    Some(pixmap)
+}
+
+// same as rasterize, one tile at a time
+pub(crate) fn rasterize_tiled(field: &dyn Field2<Color>, width: u32, height: u32, tile_size: u32) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(width, height)?;
+    for top in (0..height).step_by(tile_size as usize) {
+        for left in (0..width).step_by(tile_size as usize) {
+            let tile = IntRect::from_xywh(left as i32, top as i32, tile_size.min(width - left), tile_size.min(height - top))?;
+            let rendered = rasterize_tile(field, width, height, tile)?;
+            // copy row by row, drawing would blend
+            let pixels = pixmap.pixels_mut();
+            for (row, source) in rendered.pixels().chunks(tile.width() as usize).enumerate() {
+                let start = (top as usize + row) * width as usize + left as usize;
+                pixels[start..start + source.len()].copy_from_slice(source);
+            }
+        }
+    }
+    Some(pixmap)
+}
+
+// raises each color channel to 1 / gamma, leaving alpha alone
+// note: blending in linear light would start by undoing this with gamma below one
+pub(crate) struct GammaField {
+    field: Box<dyn Field2<Color>>,
+    gamma: f32,
+}
+impl GammaField {
+    pub fn new(field: Box<dyn Field2<Color>>, gamma: f32) -> Self {
+        Self { field, gamma: gamma.max(f32::EPSILON) }
+    }
+}
+impl Field2<Color> for GammaField {
+    fn at(&self, position: Point) -> Color {
+        let color = self.field.at(position);
+        let correct = |channel: f32| channel.clamp(0.0, 1.0).powf(1.0 / self.gamma);
+        Color::from_rgba(correct(color.red()), correct(color.green()), correct(color.blue()), color.alpha()).unwrap_or(color)
+    }
+}
+
+// scales alpha, which in premultiplied space scales every channel
+pub(crate) struct OpacityField {
+    field: Box<dyn Field2<Color>>,
+    opacity: f32,
+}
+impl OpacityField {
+    pub fn new(field: Box<dyn Field2<Color>>, opacity: f32) -> Self {
+        Self { field, opacity: opacity.clamp(0.0, 1.0) }
+    }
+}
+impl Field2<Color> for OpacityField {
+    fn at(&self, position: Point) -> Color {
+        let mut color = self.field.at(position);
+        color.apply_opacity(self.opacity);
+        color
+    }
+}
+
+// replaces color with its luminance, leaving alpha alone
+pub(crate) struct GrayscaleField {
+    field: Box<dyn Field2<Color>>,
+}
+impl GrayscaleField {
+    pub fn new(field: Box<dyn Field2<Color>>) -> Self {
+        Self { field }
+    }
+}
+impl Field2<Color> for GrayscaleField {
+    fn at(&self, position: Point) -> Color {
+        let color = self.field.at(position);
+        let gray = 0.2126 * color.red() + 0.7152 * color.green() + 0.0722 * color.blue();
+        Color::from_rgba(gray, gray, gray, color.alpha()).unwrap_or(color)
+    }
+}
+
+// darkens samples with a smooth falloff between two distances from the origin
+pub(crate) struct VignetteField {
+    field: Box<dyn Field2<Color>>,
+    inner: f32,
+    outer: f32,
+    strength: f32,
+}
+impl VignetteField {
+    pub fn new(field: Box<dyn Field2<Color>>, inner: f32, outer: f32, strength: f32) -> Self {
+        Self { field, inner, outer, strength: strength.clamp(0.0, 1.0) }
+    }
+}
+impl Field2<Color> for VignetteField {
+    fn at(&self, position: Point) -> Color {
+        let color = self.field.at(position);
+        let distance = position.x.hypot(position.y);
+        let factor = 1.0 - self.strength * smoothstep(self.inner, self.outer, distance);
+        Color::from_rgba(color.red() * factor, color.green() * factor, color.blue() * factor, color.alpha()).unwrap_or(color)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum MirrorAxes {
+    Horizontal,
+    Vertical,
+    Both,
+}
+impl MirrorAxes {
+    pub const ALL: [MirrorAxes; 3] = [MirrorAxes::Horizontal, MirrorAxes::Vertical, MirrorAxes::Both];
+    pub fn name(&self) -> &'static str {
+        match self {
+            MirrorAxes::Horizontal => "horizontal",
+            MirrorAxes::Vertical => "vertical",
+            MirrorAxes::Both => "both",
+        }
+    }
+}
+
+// reflects the negative side of the field onto the positive side through the origin
+pub(crate) struct MirrorField {
+    field: Box<dyn Field2<Color>>,
+    axes: MirrorAxes,
+}
+impl MirrorField {
+    pub fn new(field: Box<dyn Field2<Color>>, axes: MirrorAxes) -> Self {
+        Self { field, axes }
+    }
+}
+impl Field2<Color> for MirrorField {
+    fn at(&self, position: Point) -> Color {
+        let position = match self.axes {
+            MirrorAxes::Horizontal => Point::from_xy(position.x.abs(), position.y),
+            MirrorAxes::Vertical => Point::from_xy(position.x, position.y.abs()),
+            MirrorAxes::Both => Point::from_xy(position.x.abs(), position.y.abs()),
+        };
+        self.field.at(position)
+    }
+    fn constant(&self) -> Option<Color> {
+        self.field.constant()
+    }
+}
+
+// repeats the field with the given period, folding positions into the tile at the origin
+// axes with a period of zero or less are not repeated
+pub(crate) struct TileField {
+    field: Box<dyn Field2<Color>>,
+    width: f32,
+    height: f32,
+}
+impl TileField {
+    pub fn new(field: Box<dyn Field2<Color>>, width: f32, height: f32) -> Self {
+        Self { field, width, height }
+    }
+}
+impl Field2<Color> for TileField {
+    fn at(&self, position: Point) -> Color {
+        let fold = |value: f32, period: f32| if period > 0.0 { value.rem_euclid(period) } else { value };
+        self.field.at(Point::from_xy(fold(position.x, self.width), fold(position.y, self.height)))
+    }
+    fn constant(&self) -> Option<Color> {
+        self.field.constant()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum BlendMode {
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+    Difference,
+}
+impl BlendMode {
+    pub const ALL: [BlendMode; 5] = [BlendMode::Multiply, BlendMode::Screen, BlendMode::Overlay, BlendMode::Add, BlendMode::Difference];
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Overlay => "overlay",
+            BlendMode::Add => "add",
+            BlendMode::Difference => "difference",
+        }
+    }
+    // blends a base and a layer channel, both unpremultiplied in 0-1
+    fn channel(&self) -> fn(f32, f32) -> f32 {
+        match self {
+            BlendMode::Multiply => |base, layer| base * layer,
+            BlendMode::Screen => |base, layer| 1.0 - (1.0 - base) * (1.0 - layer),
+            BlendMode::Overlay => |base, layer| if base <= 0.5 { 2.0 * base * layer } else { 1.0 - 2.0 * (1.0 - base) * (1.0 - layer) },
+            BlendMode::Add => |base, layer| (base + layer).min(1.0),
+            BlendMode::Difference => |base, layer| (base - layer).abs(),
+        }
+    }
+}
+
+// composites the layer over the base with a blend mode, where only one of them covers a sample it shows unchanged
+pub(crate) struct BlendField {
+    base: Box<dyn Field2<Color>>,
+    layer: Box<dyn Field2<Color>>,
+    mode: BlendMode,
+}
+impl BlendField {
+    pub fn new(base: Box<dyn Field2<Color>>, layer: Box<dyn Field2<Color>>, mode: BlendMode) -> Self {
+        Self { base, layer, mode }
+    }
+}
+impl Field2<Color> for BlendField {
+    fn at(&self, position: Point) -> Color {
+        let (base, layer) = (self.base.at(position), self.layer.at(position));
+        let (ab, al) = (base.alpha(), layer.alpha());
+        let alpha = al + ab * (1.0 - al);
+        if alpha <= 0.0 {
+            return Color::TRANSPARENT;
+        }
+        let blend = self.mode.channel();
+        // the blended color where both overlap, each color alone elsewhere
+        let mix = |cb: f32, cl: f32| {
+            let blended = (1.0 - ab) * cl + ab * blend(cb, cl);
+            (al * blended + ab * (1.0 - al) * cb) / alpha
+        };
+        Color::from_rgba(
+            mix(base.red(), layer.red()).clamp(0.0, 1.0),
+            mix(base.green(), layer.green()).clamp(0.0, 1.0),
+            mix(base.blue(), layer.blue()).clamp(0.0, 1.0),
+            alpha.clamp(0.0, 1.0),
+        ).unwrap_or(base)
+    }
+}
+
+// encodes the sample position as red and green, normalized over the given bounds centered at the origin
+pub(crate) struct PositionField {
+    width: f32,
+    height: f32,
+}
+impl PositionField {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width: width.max(1) as f32, height: height.max(1) as f32 }
+    }
+}
+impl Field2<Color> for PositionField {
+    fn at(&self, position: Point) -> Color {
+        let u = (position.x / self.width + 0.5).clamp(0.0, 1.0);
+        let v = (position.y / self.height + 0.5).clamp(0.0, 1.0);
+        Color::from_rgba(u, v, 0.0, 1.0).unwrap_or(Color::BLACK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Color, b: Color) -> bool {
+        let delta = [a.red() - b.red(), a.green() - b.green(), a.blue() - b.blue(), a.alpha() - b.alpha()];
+        delta.iter().all(|channel| channel.abs() < 0.01)
+    }
+
+    // a black texel left of a white one
+    fn black_white(sampling: Sampling, address_mode: AddressMode) -> SampledField {
+        let mut pixmap = Pixmap::new(2, 1).unwrap();
+        pixmap.fill(Color::WHITE);
+        pixmap.pixels_mut()[0] = PremultipliedColorU8::from_rgba(0, 0, 0, 255).unwrap();
+        SampledField::new(pixmap, sampling, address_mode)
+    }
+
+    #[test]
+    fn bilinear_sampling_blends_between_texel_centers() {
+        let field = black_white(Sampling::Bilinear, AddressMode::Clamp);
+        let grey = |value: f32| Color::from_rgba(value, value, value, 1.0).unwrap();
+        assert!(close(field.at(Point::from_xy(-0.5, 0.0)), grey(0.0)));
+        assert!(close(field.at(Point::from_xy(0.5, 0.0)), grey(1.0)));
+        assert!(close(field.at(Point::from_xy(0.0, 0.0)), grey(0.5)));
+        assert!(close(field.at(Point::from_xy(-0.25, 0.0)), grey(0.25)));
+        // clamped past the edges
+        assert!(close(field.at(Point::from_xy(-3.0, 2.0)), grey(0.0)));
+        assert!(close(field.at(Point::from_xy(3.0, -2.0)), grey(1.0)));
+    }
+
+    #[test]
+    fn bilinear_sampling_fades_out_at_transparent_edges_without_darkening() {
+        let field = black_white(Sampling::Bilinear, AddressMode::Transparent);
+        // halfway between the white texel and the transparent outside
+        let edge = field.at(Point::from_xy(1.0, 0.0));
+        assert!(close(edge, Color::from_rgba(1.0, 1.0, 1.0, 0.5).unwrap()), "{:?}", edge);
+        assert_eq!(field.at(Point::from_xy(5.0, 0.0)), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn nearest_sampling_picks_the_texel_under_the_position() {
+        let field = black_white(Sampling::Nearest, AddressMode::Wrap);
+        assert_eq!(field.at(Point::from_xy(-0.1, 0.0)), Color::BLACK);
+        assert_eq!(field.at(Point::from_xy(0.1, 0.0)), Color::WHITE);
+        // wrapping repeats every two texels
+        assert_eq!(field.at(Point::from_xy(1.9, 0.0)), Color::BLACK);
+    }
+
+    #[test]
+    fn polar_field_repeats_the_first_sector() {
+        let polar = PolarField::new(Box::new(PositionField::new(8, 8)), 4.0, 0.0);
+        let inner = PositionField::new(8, 8);
+        // inside the first sector nothing changes
+        assert!(close(polar.at(Point::from_xy(1.0, 2.0)), inner.at(Point::from_xy(1.0, 2.0))));
+        // a quarter turn later lands on the same sample
+        assert!(close(polar.at(Point::from_xy(-2.0, 1.0)), inner.at(Point::from_xy(1.0, 2.0))));
+        assert!(close(polar.at(Point::from_xy(-1.0, -2.0)), inner.at(Point::from_xy(1.0, 2.0))));
+    }
+
+    #[test]
+    fn gamma_field_corrects_color_but_not_alpha() {
+        let color = Color::from_rgba(0.25, 1.0, 0.0, 0.5).unwrap();
+        let field = GammaField::new(Box::new(ConstantField::new(color)), 2.0);
+        assert!(close(field.at(Point::zero()), Color::from_rgba(0.5, 1.0, 0.0, 0.5).unwrap()));
+        // a gamma of one changes nothing
+        let field = GammaField::new(Box::new(ConstantField::new(color)), 1.0);
+        assert!(close(field.at(Point::zero()), color));
+    }
+
+    #[test]
+    fn opacity_field_scales_alpha_and_clamps() {
+        let color = Color::from_rgba(0.2, 0.4, 0.6, 0.8).unwrap();
+        let field = OpacityField::new(Box::new(ConstantField::new(color)), 0.5);
+        assert!(close(field.at(Point::zero()), Color::from_rgba(0.2, 0.4, 0.6, 0.4).unwrap()));
+        let field = OpacityField::new(Box::new(ConstantField::new(color)), 3.0);
+        assert!(close(field.at(Point::zero()), color));
+        let field = OpacityField::new(Box::new(ConstantField::new(color)), -1.0);
+        assert_eq!(field.at(Point::zero()).alpha(), 0.0);
+    }
+
+    #[test]
+    fn vignette_darkens_outside_the_inner_radius() {
+        let field = VignetteField::new(Box::new(ConstantField::new(Color::WHITE)), 10.0, 20.0, 0.75);
+        assert!(close(field.at(Point::from_xy(3.0, 4.0)), Color::WHITE));
+        // halfway through the falloff
+        let grey = |value: f32| Color::from_rgba(value, value, value, 1.0).unwrap();
+        assert!(close(field.at(Point::from_xy(0.0, -15.0)), grey(1.0 - 0.75 * 0.5)));
+        assert!(close(field.at(Point::from_xy(30.0, 0.0)), grey(0.25)));
+    }
+
+    #[test]
+    fn mirror_field_reflects_the_negative_side() {
+        let position = |x: f32, y: f32| Point::from_xy(x, y);
+        let inner = PositionField::new(8, 8);
+        let horizontal = MirrorField::new(Box::new(PositionField::new(8, 8)), MirrorAxes::Horizontal);
+        assert!(close(horizontal.at(position(-2.0, -3.0)), inner.at(position(2.0, -3.0))));
+        let vertical = MirrorField::new(Box::new(PositionField::new(8, 8)), MirrorAxes::Vertical);
+        assert!(close(vertical.at(position(-2.0, -3.0)), inner.at(position(-2.0, 3.0))));
+        let both = MirrorField::new(Box::new(PositionField::new(8, 8)), MirrorAxes::Both);
+        assert!(close(both.at(position(-2.0, -3.0)), inner.at(position(2.0, 3.0))));
+        // the positive side is left alone
+        assert!(close(both.at(position(2.0, 3.0)), inner.at(position(2.0, 3.0))));
+    }
+
+    #[test]
+    fn position_field_spans_zero_to_one_over_the_bounds() {
+        let field = PositionField::new(10, 4);
+        assert!(close(field.at(Point::from_xy(-5.0, -2.0)), Color::from_rgba(0.0, 0.0, 0.0, 1.0).unwrap()));
+        assert!(close(field.at(Point::zero()), Color::from_rgba(0.5, 0.5, 0.0, 1.0).unwrap()));
+        assert!(close(field.at(Point::from_xy(2.5, 1.0)), Color::from_rgba(0.75, 0.75, 0.0, 1.0).unwrap()));
+        // clamped outside the bounds
+        assert!(close(field.at(Point::from_xy(50.0, -9.0)), Color::from_rgba(1.0, 0.0, 0.0, 1.0).unwrap()));
+    }
+
+    #[test]
+    fn rasterize_samples_pixel_centers_around_the_origin() {
+        let pixmap = rasterize(&PositionField::new(4, 2), 4, 2).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (4, 2));
+        // pixel centers at -1.5, -0.5, 0.5 and 1.5 map to eighths
+        let red: Vec<u8> = (0..4).map(|x| pixmap.pixel(x, 0).unwrap().red()).collect();
+        assert_eq!(red, [32, 96, 159, 223]);
+        let green: Vec<u8> = (0..2).map(|y| pixmap.pixel(0, y).unwrap().green()).collect();
+        assert_eq!(green, [64, 191]);
+        assert!(rasterize(&PositionField::new(4, 2), 0, 2).is_none());
+    }
+
+    #[test]
+    fn tiled_rasterization_matches_the_whole_frame() {
+        let field = PolarField::new(Box::new(PositionField::new(50, 30)), 5.0, 0.1);
+        // neither side is a multiple of the tile size
+        for (width, height) in [(150, 70), (TILE_SIZE, TILE_SIZE), (3, 200)] {
+            let whole = rasterize(&field, width, height).unwrap();
+            let tiled = rasterize_tiled(&field, width, height, TILE_SIZE).unwrap();
+            assert_eq!(tiled.data(), whole.data(), "{}x{}", width, height);
+        }
+        let whole = rasterize(&field, 10, 7).unwrap();
+        assert_eq!(rasterize_tiled(&field, 10, 7, 3).unwrap().data(), whole.data());
+    }
+
+    #[test]
+    fn tile_field_repeats_with_its_period() {
+        let tile = TileField::new(Box::new(PositionField::new(64, 64)), 8.0, 5.0);
+        for (x, y) in [(0.5, 0.5), (3.0, 4.5), (7.9, 0.1)] {
+            let sample = tile.at(Point::from_xy(x, y));
+            for (dx, dy) in [(8.0, 0.0), (0.0, 5.0), (-16.0, -10.0), (24.0, 35.0)] {
+                assert!(close(tile.at(Point::from_xy(x + dx, y + dy)), sample), "{} {} {} {}", x, y, dx, dy);
+            }
+        }
+        // a zero period leaves that axis alone
+        let rows = TileField::new(Box::new(PositionField::new(64, 64)), 0.0, 5.0);
+        assert!(!close(rows.at(Point::from_xy(1.0, 1.0)), rows.at(Point::from_xy(9.0, 1.0))));
+        assert!(close(rows.at(Point::from_xy(1.0, 1.0)), rows.at(Point::from_xy(1.0, 6.0))));
+    }
+
+    fn blend(base: Color, layer: Color, mode: BlendMode) -> Color {
+        BlendField::new(Box::new(ConstantField::new(base)), Box::new(ConstantField::new(layer)), mode).at(Point::zero())
+    }
+
+    #[test]
+    fn blend_identities() {
+        let grey = Color::from_rgba(0.5, 0.5, 0.5, 1.0).unwrap();
+        for base in [Color::from_rgba(0.2, 0.6, 0.9, 1.0).unwrap(), Color::from_rgba(0.8, 0.1, 0.5, 1.0).unwrap()] {
+            assert!(close(blend(base, Color::WHITE, BlendMode::Multiply), base));
+            assert!(close(blend(base, Color::BLACK, BlendMode::Screen), base));
+            assert!(close(blend(base, Color::BLACK, BlendMode::Add), base));
+            assert!(close(blend(base, base, BlendMode::Difference), Color::BLACK));
+            // overlay with 50% grey changes nothing on either side of the midpoint
+            assert!(close(blend(base, grey, BlendMode::Overlay), base));
+        }
+    }
+
+    #[test]
+    fn blend_shows_each_side_alone_where_the_other_is_transparent() {
+        let color = Color::from_rgba(0.2, 0.6, 0.9, 1.0).unwrap();
+        for mode in BlendMode::ALL {
+            assert!(close(blend(color, Color::TRANSPARENT, mode), color), "{:?}", mode);
+            assert!(close(blend(Color::TRANSPARENT, color, mode), color), "{:?}", mode);
+            assert_eq!(blend(Color::TRANSPARENT, Color::TRANSPARENT, mode), Color::TRANSPARENT);
+        }
+    }
+
+    #[test]
+    fn grayscale_field_keeps_luminance_and_alpha() {
+        let field = GrayscaleField::new(Box::new(ConstantField::new(Color::from_rgba(1.0, 0.0, 0.0, 0.5).unwrap())));
+        assert!(close(field.at(Point::zero()), Color::from_rgba(0.2126, 0.2126, 0.2126, 0.5).unwrap()));
+        let field = GrayscaleField::new(Box::new(ConstantField::new(Color::WHITE)));
+        assert!(close(field.at(Point::zero()), Color::WHITE));
+    }
 }
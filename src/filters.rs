@@ -0,0 +1,470 @@
+use tiny_skia::{Color, ColorU8, Pixmap, PixmapPaint, Point, PremultipliedColorU8, Transform};
+
+use crate::fields::{AddressMode, Field2, SampledField, Sampling};
+
+// averages each block of pixels, blocks along the right and bottom edges may be smaller
+pub(crate) fn pixelate(pixmap: &Pixmap, block_size: u32) -> Pixmap {
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let block_size = block_size.max(1);
+    let mut result = pixmap.clone();
+    for top in (0..height).step_by(block_size as usize) {
+        for left in (0..width).step_by(block_size as usize) {
+            let xs = left..(left + block_size).min(width);
+            let ys = top..(top + block_size).min(height);
+            let mut sum = [0u32; 4];
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    let pixel = pixmap.pixels()[(y * width + x) as usize];
+                    sum[0] += pixel.red() as u32;
+                    sum[1] += pixel.green() as u32;
+                    sum[2] += pixel.blue() as u32;
+                    sum[3] += pixel.alpha() as u32;
+                }
+            }
+            let count = xs.len() as u32 * ys.len() as u32;
+            let [r, g, b, a] = sum.map(|channel| ((channel + count / 2) / count) as u8);
+            let average = PremultipliedColorU8::from_rgba(r, g, b, a).unwrap_or(PremultipliedColorU8::TRANSPARENT);
+            let pixels = result.pixels_mut();
+            for y in ys {
+                for x in xs.clone() {
+                    pixels[(y * width + x) as usize] = average;
+                }
+            }
+        }
+    }
+    result
+}
+
+// luminance of premultiplied pixels, so transparent areas count as black
+fn luminance(pixmap: &Pixmap) -> Vec<f32> {
+    pixmap.pixels()
+        .iter()
+        .map(|pixel| (0.2126 * pixel.red() as f32 + 0.7152 * pixel.green() as f32 + 0.0722 * pixel.blue() as f32) / 255.0)
+        .collect()
+}
+
+// sobel gradient magnitude as opaque grayscale, or a black and white mask when threshold is positive
+pub(crate) fn edge_detect(pixmap: &Pixmap, threshold: f32) -> Pixmap {
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let luminance = luminance(pixmap);
+    // samples outside the pixmap are clamped to the border
+    let at = |x: i32, y: i32| luminance[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+    let mut result = pixmap.clone();
+    for (index, pixel) in result.pixels_mut().iter_mut().enumerate() {
+        let (x, y) = (index as i32 % width, index as i32 / width);
+        let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+            - at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1);
+        let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+            - at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1);
+        let magnitude = (gx * gx + gy * gy).sqrt();
+        let value = if threshold > 0.0 {
+            if magnitude >= threshold { 1.0 } else { 0.0 }
+        } else {
+            magnitude.min(1.0)
+        };
+        let gray = (255.0 * value).round() as u8;
+        *pixel = PremultipliedColorU8::from_rgba(gray, gray, gray, 255).unwrap();
+    }
+    result
+}
+
+// offsets each source sample by the red and green channels of the displacement, centered at 0.5
+pub(crate) fn displace(source: Pixmap, displacement: &Pixmap, strength: f32, address_mode: AddressMode) -> Pixmap {
+    let (width, height) = (source.width(), source.height());
+    let mut result = Pixmap::new(width, height).unwrap();
+    let field = SampledField::new(source, Sampling::Bilinear, address_mode);
+    for (index, pixel) in result.pixels_mut().iter_mut().enumerate() {
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        // transparent or missing displacement leaves the sample in place
+        let offset = displacement.pixel(x, y).filter(|offset| offset.alpha() > 0);
+        let (dx, dy) = offset.map_or((0.0, 0.0), |offset| {
+            let offset = offset.demultiply();
+            (offset.red() as f32 / 255.0 - 0.5, offset.green() as f32 / 255.0 - 0.5)
+        });
+        // sampled fields are centered around the origin
+        let position = Point::from_xy(
+            x as f32 + 0.5 + strength * dx - 0.5 * width as f32,
+            y as f32 + 0.5 + strength * dy - 0.5 * height as f32,
+        );
+        *pixel = field.at(position).premultiply().to_color_u8();
+    }
+    result
+}
+
+// darkens the last row of every spacing rows, scaling color but not alpha so it stays premultiplied
+pub(crate) fn scanlines(pixmap: &Pixmap, factor: f32, spacing: u32) -> Pixmap {
+    let width = pixmap.width() as usize;
+    let spacing = spacing.max(1) as usize;
+    let factor = factor.clamp(0.0, 1.0);
+    let mut result = pixmap.clone();
+    for (y, row) in result.pixels_mut().chunks_mut(width).enumerate() {
+        if y % spacing != spacing - 1 {
+            continue;
+        }
+        for pixel in row {
+            let scale = |channel: u8| (channel as f32 * factor).round() as u8;
+            *pixel = PremultipliedColorU8::from_rgba(scale(pixel.red()), scale(pixel.green()), scale(pixel.blue()), pixel.alpha())
+                .unwrap_or(*pixel);
+        }
+    }
+    result
+}
+
+// multiplies the color channels by factors in 0-1, which keeps premultiplied pixels valid
+pub(crate) fn tint(pixmap: &Pixmap, factors: [f32; 3]) -> Pixmap {
+    let [r, g, b] = factors.map(|factor| factor.clamp(0.0, 1.0));
+    let mut result = pixmap.clone();
+    for pixel in result.pixels_mut() {
+        let scale = |channel: u8, factor: f32| (channel as f32 * factor).round() as u8;
+        *pixel = PremultipliedColorU8::from_rgba(scale(pixel.red(), r), scale(pixel.green(), g), scale(pixel.blue(), b), pixel.alpha())
+            .unwrap_or(*pixel);
+    }
+    result
+}
+
+// copies a region of the pixmap, parts of the region outside the source are transparent
+pub(crate) fn crop(pixmap: &Pixmap, left: i32, top: i32, width: u32, height: u32) -> Option<Pixmap> {
+    let mut result = Pixmap::new(width, height)?;
+    // copy the overlap row by row, drawing would pad the source edges into the region
+    let (source_width, source_height) = (pixmap.width() as i64, pixmap.height() as i64);
+    let (left, top) = (left as i64, top as i64);
+    let xs = left.max(0)..(left + width as i64).min(source_width);
+    if xs.is_empty() {
+        return Some(result);
+    }
+    for y in top.max(0)..(top + height as i64).min(source_height) {
+        let source = &pixmap.pixels()[(y * source_width + xs.start) as usize..(y * source_width + xs.end) as usize];
+        let start = ((y - top) * width as i64 + xs.start - left) as usize;
+        result.pixels_mut()[start..start + source.len()].copy_from_slice(source);
+    }
+    Some(result)
+}
+
+// composites the pixmap over an opaque fill, the alpha of the color is ignored so the result is opaque
+pub(crate) fn background(pixmap: &Pixmap, color: Color) -> Pixmap {
+    let mut result = Pixmap::new(pixmap.width(), pixmap.height()).unwrap();
+    let mut opaque = color;
+    opaque.set_alpha(1.0);
+    result.fill(opaque);
+    result.draw_pixmap(0, 0, pixmap.as_ref(), &PixmapPaint::default(), Transform::identity(), None);
+    result
+}
+
+// snaps every pixel to the nearest palette color by rgb distance, alpha is kept
+pub(crate) fn quantize(pixmap: &Pixmap, palette: &[Color]) -> Pixmap {
+    let palette: Vec<ColorU8> = palette.iter().map(|color| color.to_color_u8()).collect();
+    let mut result = pixmap.clone();
+    if palette.is_empty() {
+        return result;
+    }
+    for pixel in result.pixels_mut() {
+        let color = pixel.demultiply();
+        let nearest = palette[nearest(&palette, color).unwrap()];
+        *pixel = ColorU8::from_rgba(nearest.red(), nearest.green(), nearest.blue(), color.alpha()).premultiply();
+    }
+    result
+}
+
+// index of the palette color nearest to the color by rgb distance, alpha is ignored
+pub(crate) fn nearest(palette: &[ColorU8], color: ColorU8) -> Option<usize> {
+    let distance = |entry: &ColorU8| {
+        let delta = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        delta(color.red(), entry.red()) + delta(color.green(), entry.green()) + delta(color.blue(), entry.blue())
+    };
+    palette.iter().enumerate().min_by_key(|(_, entry)| distance(entry)).map(|(index, _)| index)
+}
+
+// shifts red by the offset and blue against it, alpha follows the unshifted green sample
+pub(crate) fn chromatic_aberration(pixmap: &Pixmap, dx: f32, dy: f32) -> Pixmap {
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let (dx, dy) = (dx.round() as i32, dy.round() as i32);
+    // samples outside the pixmap are clamped to the border
+    let at = |x: i32, y: i32| pixmap.pixels()[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+    let mut result = pixmap.clone();
+    for (index, pixel) in result.pixels_mut().iter_mut().enumerate() {
+        let (x, y) = (index as i32 % width, index as i32 / width);
+        let green = at(x, y);
+        let alpha = green.alpha();
+        // keep the channels premultiplied by the green alpha
+        let red = at(x + dx, y + dy).red().min(alpha);
+        let blue = at(x - dx, y - dy).blue().min(alpha);
+        *pixel = PremultipliedColorU8::from_rgba(red, green.green(), blue, alpha).unwrap_or(green);
+    }
+    result
+}
+
+// premultiplied channels as floats in 0..=255
+fn channels(pixel: PremultipliedColorU8) -> [f32; 4] {
+    [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()].map(|channel| channel as f32)
+}
+
+fn from_channels([r, g, b, a]: [f32; 4]) -> PremultipliedColorU8 {
+    let a = a.round().clamp(0.0, 255.0);
+    // color can't exceed alpha when premultiplied
+    let channel = |c: f32| c.round().clamp(0.0, a) as u8;
+    PremultipliedColorU8::from_rgba(channel(r), channel(g), channel(b), a as u8).unwrap_or(PremultipliedColorU8::TRANSPARENT)
+}
+
+// separable gaussian blur in premultiplied space, clamping at the borders
+pub(crate) fn gaussian_blur(pixmap: &Pixmap, radius: u32) -> Pixmap {
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let radius = radius as i32;
+    let sigma = (radius as f32 / 2.0).max(0.5);
+    let weights: Vec<f32> = (-radius..=radius).map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp()).collect();
+    let total: f32 = weights.iter().sum();
+    let pass = |source: &[[f32; 4]], dx: i32, dy: i32| -> Vec<[f32; 4]> {
+        (0..width * height)
+            .map(|index| {
+                let (x, y) = (index % width, index / width);
+                let mut sum = [0.0; 4];
+                for (i, weight) in (-radius..=radius).zip(&weights) {
+                    let sx = (x + i * dx).clamp(0, width - 1);
+                    let sy = (y + i * dy).clamp(0, height - 1);
+                    let sample = source[(sy * width + sx) as usize];
+                    for channel in 0..4 {
+                        sum[channel] += weight * sample[channel];
+                    }
+                }
+                sum.map(|channel| channel / total)
+            })
+            .collect()
+    };
+    let source: Vec<[f32; 4]> = pixmap.pixels().iter().map(|pixel| channels(*pixel)).collect();
+    let blurred = pass(&pass(&source, 1, 0), 0, 1);
+    let mut result = pixmap.clone();
+    for (pixel, value) in result.pixels_mut().iter_mut().zip(blurred) {
+        *pixel = from_channels(value);
+    }
+    result
+}
+
+// blurs the pixels brighter than threshold and adds them back on top
+pub(crate) fn bloom(pixmap: &Pixmap, threshold: f32, intensity: f32) -> Pixmap {
+    let mut bright = pixmap.clone();
+    for pixel in bright.pixels_mut() {
+        let [r, g, b, _] = channels(*pixel);
+        if (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.0 < threshold {
+            *pixel = PremultipliedColorU8::TRANSPARENT;
+        }
+    }
+    let glow = gaussian_blur(&bright, 8);
+    let mut result = pixmap.clone();
+    for (pixel, glow) in result.pixels_mut().iter_mut().zip(glow.pixels()) {
+        let (source, glow) = (channels(*pixel), channels(*glow));
+        *pixel = from_channels([0, 1, 2, 3].map(|i| source[i] + intensity * glow[i]));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(width: u32, height: u32, color: Color) -> Pixmap {
+        let mut pixmap = Pixmap::new(width, height).unwrap();
+        pixmap.fill(color);
+        pixmap
+    }
+
+    fn rgba(pixmap: &Pixmap, x: u32, y: u32) -> [u8; 4] {
+        let pixel = pixmap.pixel(x, y).unwrap().demultiply();
+        [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+    }
+
+    #[test]
+    fn pixelate_averages_each_block() {
+        // a black and white checkerboard averages to gray
+        let mut pixmap = filled(4, 4, Color::BLACK);
+        for (index, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+            if (index % 4 + index / 4) % 2 == 0 {
+                *pixel = PremultipliedColorU8::from_rgba(255, 255, 255, 255).unwrap();
+            }
+        }
+        let result = pixelate(&pixmap, 2);
+        for (x, y) in [(0, 0), (1, 1), (3, 0), (2, 3)] {
+            assert_eq!(rgba(&result, x, y), [128, 128, 128, 255]);
+        }
+    }
+
+    #[test]
+    fn pixelate_handles_partial_blocks_at_the_edges() {
+        let mut pixmap = filled(3, 1, Color::BLACK);
+        pixmap.pixels_mut()[2] = PremultipliedColorU8::from_rgba(255, 0, 0, 255).unwrap();
+        let result = pixelate(&pixmap, 2);
+        assert_eq!(rgba(&result, 0, 0), [0, 0, 0, 255]);
+        // the last block is a single pixel and keeps its color
+        assert_eq!(rgba(&result, 2, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn edge_detect_is_zero_on_flat_input() {
+        let result = edge_detect(&filled(5, 5, Color::from_rgba8(90, 140, 200, 255)), 0.0);
+        assert!(result.pixels().iter().all(|pixel| (pixel.red(), pixel.alpha()) == (0, 255)));
+    }
+
+    #[test]
+    fn edge_detect_finds_a_vertical_edge() {
+        // black left half, white right half
+        let mut pixmap = filled(4, 3, Color::BLACK);
+        for (index, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+            if index % 4 >= 2 {
+                *pixel = PremultipliedColorU8::from_rgba(255, 255, 255, 255).unwrap();
+            }
+        }
+        let result = edge_detect(&pixmap, 0.0);
+        assert_eq!(rgba(&result, 0, 1), [0, 0, 0, 255]);
+        assert_eq!(rgba(&result, 1, 1), [255, 255, 255, 255]);
+        assert_eq!(rgba(&result, 2, 1), [255, 255, 255, 255]);
+        assert_eq!(rgba(&result, 3, 1), [0, 0, 0, 255]);
+
+        // a threshold above the gradient removes the edge
+        let masked = edge_detect(&pixmap, 5.0);
+        assert!(masked.pixels().iter().all(|pixel| pixel.red() == 0));
+    }
+
+    // a row of pixels getting brighter to the right
+    fn ramp(width: u32) -> Pixmap {
+        let mut pixmap = Pixmap::new(width, 1).unwrap();
+        for (x, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+            let value = (x * 60) as u8;
+            *pixel = PremultipliedColorU8::from_rgba(value, value, value, 255).unwrap();
+        }
+        pixmap
+    }
+
+    #[test]
+    fn neutral_displacement_is_identity() {
+        let source = ramp(4);
+        let neutral = filled(4, 1, Color::from_rgba8(128, 128, 0, 255));
+        let result = displace(source.clone(), &neutral, 8.0, AddressMode::Clamp);
+        for x in 0..4 {
+            let (a, b) = (rgba(&result, x, 0), rgba(&source, x, 0));
+            assert!(a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 2), "{:?} {:?}", a, b);
+        }
+        // transparent displacement leaves the samples exactly in place
+        let result = displace(source.clone(), &Pixmap::new(4, 1).unwrap(), 8.0, AddressMode::Clamp);
+        assert_eq!(result.data(), source.data());
+    }
+
+    #[test]
+    fn full_red_displacement_shifts_samples() {
+        let source = ramp(4);
+        // red at full strength offsets by half the strength
+        let right = filled(4, 1, Color::from_rgba8(255, 128, 0, 255));
+        let result = displace(source.clone(), &right, 2.0, AddressMode::Clamp);
+        for x in 0..3 {
+            assert_eq!(rgba(&result, x, 0), rgba(&source, x + 1, 0));
+        }
+        assert_eq!(rgba(&result, 3, 0), rgba(&source, 3, 0));
+    }
+
+    #[test]
+    fn scanlines_darken_every_spacing_row() {
+        let result = scanlines(&filled(2, 6, Color::from_rgba8(200, 100, 50, 255)), 0.5, 3);
+        for y in 0..6 {
+            let expected = if y % 3 == 2 { [100, 50, 25, 255] } else { [200, 100, 50, 255] };
+            assert_eq!(rgba(&result, 1, y), expected, "row {}", y);
+        }
+    }
+
+    #[test]
+    fn scanlines_keep_alpha() {
+        let pixmap = filled(1, 2, Color::from_rgba8(255, 255, 255, 128));
+        let result = scanlines(&pixmap, 0.0, 2);
+        assert_eq!(result.pixel(0, 1).unwrap().alpha(), 128);
+        assert_eq!(result.pixel(0, 1).unwrap().red(), 0);
+        assert_eq!(result.pixel(0, 0), pixmap.pixel(0, 0));
+    }
+
+    #[test]
+    fn chromatic_aberration_shifts_red_and_blue_apart() {
+        // a single white pixel on black
+        let mut pixmap = filled(5, 1, Color::BLACK);
+        pixmap.pixels_mut()[2] = PremultipliedColorU8::from_rgba(255, 255, 255, 255).unwrap();
+        let result = chromatic_aberration(&pixmap, 1.0, 0.0);
+        assert_eq!(rgba(&result, 1, 0), [255, 0, 0, 255]);
+        assert_eq!(rgba(&result, 2, 0), [0, 255, 0, 255]);
+        assert_eq!(rgba(&result, 3, 0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn chromatic_aberration_without_offset_is_identity() {
+        let pixmap = filled(3, 3, Color::from_rgba8(10, 20, 30, 100));
+        assert_eq!(chromatic_aberration(&pixmap, 0.0, 0.0).data(), pixmap.data());
+    }
+
+    #[test]
+    fn blur_keeps_a_solid_color() {
+        let pixmap = filled(7, 5, Color::from_rgba8(40, 160, 220, 200));
+        let result = gaussian_blur(&pixmap, 3);
+        assert_eq!(result.data(), pixmap.data());
+    }
+
+    #[test]
+    fn blur_spreads_a_single_pixel() {
+        let mut pixmap = Pixmap::new(5, 5).unwrap();
+        pixmap.pixels_mut()[12] = PremultipliedColorU8::from_rgba(255, 255, 255, 255).unwrap();
+        let result = gaussian_blur(&pixmap, 2);
+        let center = result.pixel(2, 2).unwrap().alpha();
+        let side = result.pixel(3, 2).unwrap().alpha();
+        assert!(center < 255 && side > 0 && side < center, "{} {}", center, side);
+    }
+
+    #[test]
+    fn bloom_ignores_pixels_below_the_threshold() {
+        let pixmap = filled(4, 4, Color::from_rgba8(50, 50, 50, 255));
+        assert_eq!(bloom(&pixmap, 0.5, 1.0).data(), pixmap.data());
+    }
+
+    #[test]
+    fn bloom_brightens_around_bright_pixels() {
+        let mut pixmap = filled(9, 9, Color::BLACK);
+        pixmap.pixels_mut()[4 * 9 + 4] = PremultipliedColorU8::from_rgba(255, 255, 255, 255).unwrap();
+        let result = bloom(&pixmap, 0.5, 1.0);
+        assert!(result.pixel(5, 4).unwrap().red() > 0);
+        assert_eq!(rgba(&result, 4, 4), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn quantize_maps_to_the_nearest_palette_entry() {
+        let palette = [Color::BLACK, Color::WHITE, Color::from_rgba8(255, 0, 0, 255)];
+        let result = quantize(&filled(2, 2, Color::from_rgba8(200, 40, 30, 255)), &palette);
+        assert_eq!(rgba(&result, 1, 1), [255, 0, 0, 255]);
+        let result = quantize(&filled(2, 2, Color::from_rgba8(90, 90, 90, 255)), &palette);
+        assert_eq!(rgba(&result, 0, 0), [0, 0, 0, 255]);
+        // alpha is kept
+        let result = quantize(&filled(1, 1, Color::from_rgba8(250, 250, 240, 128)), &palette);
+        assert_eq!(rgba(&result, 0, 0), [255, 255, 255, 128]);
+        // an empty palette changes nothing
+        let pixmap = filled(1, 1, Color::from_rgba8(1, 2, 3, 255));
+        assert_eq!(quantize(&pixmap, &[]).data(), pixmap.data());
+    }
+
+    #[test]
+    fn background_composites_translucent_pixels_into_opaque_ones() {
+        let pixmap = filled(2, 2, Color::from_rgba8(255, 255, 255, 128));
+        let result = background(&pixmap, Color::from_rgba8(0, 0, 255, 255));
+        assert_eq!(rgba(&result, 1, 1), [128, 128, 255, 255]);
+        // the alpha of the background color is ignored
+        let result = background(&Pixmap::new(1, 1).unwrap(), Color::from_rgba8(255, 0, 0, 10));
+        assert_eq!(rgba(&result, 0, 0), [255, 0, 0, 255]);
+        let opaque = filled(1, 1, Color::from_rgba8(0, 200, 0, 255));
+        assert_eq!(background(&opaque, Color::WHITE).data(), opaque.data());
+    }
+
+    #[test]
+    fn crop_outside_the_source_is_transparent() {
+        let pixmap = filled(4, 4, Color::from_rgba8(0, 0, 255, 255));
+        let result = crop(&pixmap, 2, -1, 4, 3).unwrap();
+        assert_eq!((result.width(), result.height()), (4, 3));
+        assert_eq!(rgba(&result, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(rgba(&result, 1, 1), [0, 0, 255, 255]);
+        assert_eq!(rgba(&result, 2, 1), [0, 0, 0, 0]);
+        // nothing spills past the bottom right corner
+        let corner = crop(&pixmap, 3, 3, 2, 2).unwrap();
+        assert_eq!(corner.pixels().iter().map(|pixel| pixel.alpha()).collect::<Vec<_>>(), [255, 0, 0, 0]);
+        // entirely outside
+        assert!(crop(&pixmap, 10, 10, 2, 2).unwrap().pixels().iter().all(|pixel| pixel.alpha() == 0));
+        assert!(crop(&pixmap, 0, 0, 0, 2).is_none());
+    }
+}
@@ -1,64 +1,445 @@
-use tiny_skia::{Color, FillRule, Paint, Path, PathBuilder, Pixmap, Point, Rect, Transform};
+use std::ops::Range;
+
+use tiny_skia::{Color, FillRule, Paint, Path, PathBuilder, Pixmap, Point, Rect, Stroke, Transform};
 
 use crate::fields::Field2;
 
-fn hex_tile(size: f32) -> Path {
-    // pointy top
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub enum Orientation {
+    PointyTop,
+    FlatTop,
+}
+impl Orientation {
+    pub const ALL: [Orientation; 2] = [Orientation::PointyTop, Orientation::FlatTop];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Orientation::PointyTop => "pointy",
+            Orientation::FlatTop => "flat",
+        }
+    }
+}
+
+fn hex_corners(size: f32, orientation: Orientation) -> [Point; 6] {
     let w = 3.0_f32.sqrt() / 2.0 * size;
+    match orientation {
+        Orientation::PointyTop => [
+            Point::from_xy(0.0, -size), // top
+            Point::from_xy(w, - 0.5 * size), // top right
+            Point::from_xy(w, 0.5 * size), // bottom right
+            Point::from_xy(0.0, size), // bottom
+            Point::from_xy(- w, 0.5 * size), // bottom left
+            Point::from_xy(- w, -0.5 * size), // top left
+        ],
+        Orientation::FlatTop => [
+            Point::from_xy(size, 0.0), // right
+            Point::from_xy(0.5 * size, w), // bottom right
+            Point::from_xy(-0.5 * size, w), // bottom left
+            Point::from_xy(-size, 0.0), // left
+            Point::from_xy(-0.5 * size, -w), // top left
+            Point::from_xy(0.5 * size, -w), // top right
+        ],
+    }
+}
+
+fn hex_tile(size: f32, orientation: Orientation) -> Path {
+    let [first, rest @ ..] = hex_corners(size, orientation);
     let mut pb = PathBuilder::with_capacity(7, 6);
-    pb.move_to(0.0, -size); // top
-    pb.line_to(w, - 0.5 * size); // top right
-    pb.line_to(w, 0.5 * size); // bottom right
-    pb.line_to(0.0, size); // bottom
-    pb.line_to(- w, 0.5 * size); // bottom left
-    pb.line_to(- w, -0.5 * size); // top left
+    pb.move_to(first.x, first.y);
+    for corner in rest {
+        pb.line_to(corner.x, corner.y);
+    }
     pb.close();
     pb.finish().unwrap()
 }
 
+// smallest spacing and size, so a slider at zero can't ask for endless tiles
+const MIN_SPACING: f32 = 0.5;
+// grids needing more tiles than this to cover the screen are not drawn
+const MAX_TILES: i64 = 250_000;
+
 pub struct HexGrid {
     spacing: f32,
     size: f32,
     transform: Transform,
+    orientation: Orientation,
+    // smooth tile edges, at the cost of faint seams between tiles
+    anti_alias: bool,
+    // outline color and width in pixels, drawn on top of the fill
+    stroke: Option<(Color, f32)>,
 }
 impl HexGrid {
-    pub fn new(spacing: f32, size: f32, transform: Transform) -> Self {
-        Self { spacing, size, transform }
+    pub fn new(spacing: f32, size: f32, transform: Transform, orientation: Orientation) -> Self {
+        let (spacing, size) = (spacing.max(MIN_SPACING), size.max(MIN_SPACING));
+        Self { spacing, size, transform, orientation, anti_alias: true, stroke: None }
+    }
+    pub fn with_anti_alias(self, anti_alias: bool) -> Self {
+        Self { anti_alias, ..self }
+    }
+    // outlines are left out when they would not show
+    pub fn with_stroke(self, color: Color, width: f32) -> Self {
+        let stroke = (width > 0.0 && color.alpha() > 0.0).then_some((color, width));
+        Self { stroke, ..self }
+    }
+    // how far tiles reach from their center on screen, outlines included
+    fn screen_margin(&self) -> f32 {
+        self.size.abs() * self.max_scale() + self.stroke.map_or(0.0, |(_, width)| 0.5 * width)
+    }
+    // distance between neighbouring tile centers along x and y for a unit index step
+    fn steps(&self) -> (f32, f32) {
+        match self.orientation {
+            Orientation::PointyTop => (self.spacing * 3.0_f32.sqrt(), self.spacing * 3.0/2.0),
+            Orientation::FlatTop => (self.spacing * 3.0/2.0, self.spacing * 3.0_f32.sqrt()),
+        }
+    }
+    // tile indices covering the rect in grid space, with a margin of a tile on each side
+    fn tile_range(&self, rect: &Rect) -> (Range<i32>, Range<i32>) {
+        let (dx, dy) = self.steps();
+        let margin = self.size.abs();
+        let q0 = (((rect.left() - margin) / dx).floor() as i32).saturating_sub(1);
+        let q1 = (((rect.right() + margin) / dx).ceil() as i32).saturating_add(1);
+        let r0 = (((rect.top() - margin) / dy).floor() as i32).saturating_sub(1);
+        let r1 = (((rect.bottom() + margin) / dy).ceil() as i32).saturating_add(1);
+        (q0..q1, r0..r1)
+    }
+    // largest scale factor of the grid transform, used to size tiles on screen
+    fn max_scale(&self) -> f32 {
+        let ts = self.transform;
+        let sx = (ts.sx * ts.sx + ts.ky * ts.ky).sqrt();
+        let sy = (ts.kx * ts.kx + ts.sy * ts.sy).sqrt();
+        sx.max(sy)
     }
+    // center of the tile at offset coordinates in grid space, odd rows (pointy) or columns (flat) are shifted
     fn position(&self, q: i32, r: i32) -> Point {
-        let x = self.spacing * 3.0_f32.sqrt() * (q as f32 + 0.5 * (r & 1) as f32);
-        let y = self.spacing * 3.0/2.0 * r as f32;
-        Point { x, y }
+        self.axial_position(self.offset_to_axial((q, r)))
+    }
+    // offset coordinates (column, row) to axial coordinates (q, r)
+    pub fn offset_to_axial(&self, (column, row): (i32, i32)) -> (i32, i32) {
+        match self.orientation {
+            // odd rows are shifted right
+            Orientation::PointyTop => (column - (row - (row & 1)) / 2, row),
+            // odd columns are shifted down
+            Orientation::FlatTop => (column, row - (column - (column & 1)) / 2),
+        }
+    }
+    // axial coordinates (q, r) to offset coordinates (column, row)
+    pub fn axial_to_offset(&self, (q, r): (i32, i32)) -> (i32, i32) {
+        match self.orientation {
+            Orientation::PointyTop => (q + (r - (r & 1)) / 2, r),
+            Orientation::FlatTop => (q, r + (q - (q & 1)) / 2),
+        }
+    }
+    // center of the tile at axial coordinates in grid space
+    pub fn axial_position(&self, (q, r): (i32, i32)) -> Point {
+        match self.orientation {
+            Orientation::PointyTop => {
+                let x = self.spacing * 3.0_f32.sqrt() * (q as f32 + 0.5 * r as f32);
+                let y = self.spacing * 3.0/2.0 * r as f32;
+                Point { x, y }
+            },
+            Orientation::FlatTop => {
+                let x = self.spacing * 3.0/2.0 * q as f32;
+                let y = self.spacing * 3.0_f32.sqrt() * (r as f32 + 0.5 * q as f32);
+                Point { x, y }
+            },
+        }
+    }
+    // axial coordinates of the tile whose center is nearest to a point in grid space
+    pub fn axial_at(&self, point: Point) -> (i32, i32) {
+        let (q, r) = match self.orientation {
+            Orientation::PointyTop => (
+                (3.0_f32.sqrt() / 3.0 * point.x - point.y / 3.0) / self.spacing,
+                (2.0 / 3.0 * point.y) / self.spacing,
+            ),
+            Orientation::FlatTop => (
+                (2.0 / 3.0 * point.x) / self.spacing,
+                (3.0_f32.sqrt() / 3.0 * point.y - point.x / 3.0) / self.spacing,
+            ),
+        };
+        // round in cube coordinates, fixing up the component that moved the most
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        (rq as i32, rr as i32)
+    }
+    // offset coordinates of the tile under a point on screen, None when the grid transform can't be inverted
+    pub fn hex_at(&self, point: Point) -> Option<(i32, i32)> {
+        let mut point = point;
+        self.transform.invert()?.map_point(&mut point);
+        Some(self.axial_to_offset(self.axial_at(point)))
+    }
+}
+
+// centers and colors of all tiles that can touch a width x height screen, in drawing order
+// the color field is sampled at each tile center, fully transparent tiles are left out unless outlined
+fn visible_tiles(width: u32, height: u32, grid: &HexGrid, color_field: &dyn Field2<Color>) -> Result<Vec<(Point, Color)>, String> {
+    let screen = Rect::from_xywh(0.0, 0.0, width as f32, height as f32).unwrap();
+    // the bounding box of the screen in grid space, covers rotated grids too
+    // a grid scaled to nothing covers no tiles
+    let Some(rect) = grid.transform.invert().and_then(|inverse| screen.transform(inverse)) else {
+        return Ok(Vec::new());
+    };
+    // skip tiles that can't touch the screen
+    let margin = grid.screen_margin();
+    let visible_x = screen.left() - margin..=screen.right() + margin;
+    let visible_y = screen.top() - margin..=screen.bottom() + margin;
+    let (qs, rs) = grid.tile_range(&rect);
+    let count = (qs.end as i64 - qs.start as i64) * (rs.end as i64 - rs.start as i64);
+    if count > MAX_TILES {
+        return Err(format!("the hex grid needs {} tiles, at most {} are drawn", count, MAX_TILES));
+    }
+    // constant fields are not sampled per tile
+    let constant = color_field.constant();
+    let outlined = grid.stroke.is_some();
+    if !outlined && constant.is_some_and(|color| color.alpha() <= 0.0) {
+        return Ok(Vec::new());
+    }
+    let mut tiles = Vec::new();
+    for r in rs {
+        for q in qs.clone() {
+            let p = grid.position(q, r);
+            let mut center = p;
+            grid.transform.map_point(&mut center);
+            if !visible_x.contains(&center.x) || !visible_y.contains(&center.y) {
+                continue;
+            }
+            let color = constant.unwrap_or_else(|| color_field.at(p));
+            if outlined || color.alpha() > 0.0 {
+                tiles.push((p, color));
+            }
+        }
+    }
+    Ok(tiles)
+}
+
+fn fill_tile(pixmap: &mut Pixmap, hex_tile: &Path, color: Color, transform: Transform, anti_alias: bool) {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = anti_alias;
+    pixmap.fill_path(hex_tile, &paint, FillRule::Winding, transform, None);
+}
+
+// the path is transformed before stroking so the width stays in pixels
+fn stroke_tile(pixmap: &mut Pixmap, hex_tile: &Path, (color, width): (Color, f32), transform: Transform, anti_alias: bool) {
+    let Some(path) = hex_tile.clone().transform(transform) else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = anti_alias;
+    let stroke = Stroke { width, ..Stroke::default() };
+    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+}
+
+// fills every tile before outlining any, so neighbouring fills don't cover the outlines
+fn draw_tiles(pixmap: &mut Pixmap, grid: &HexGrid, hex_tile: &Path, tiles: &[&(Point, Color)], offset: Transform) {
+    for (p, color) in tiles {
+        if color.alpha() > 0.0 {
+            fill_tile(pixmap, hex_tile, *color, grid.transform.pre_translate(p.x, p.y).post_concat(offset), grid.anti_alias);
+        }
+    }
+    if let Some(stroke) = grid.stroke {
+        for (p, _) in tiles {
+            stroke_tile(pixmap, hex_tile, stroke, grid.transform.pre_translate(p.x, p.y).post_concat(offset), grid.anti_alias);
+        }
     }
 }
 
-fn bounds_for(pixmap: &Pixmap) -> Rect {
-    Rect::from_xywh(0.0, 0.0, pixmap.width() as f32, pixmap.height() as f32).unwrap()
+// leaves the pixmap untouched when the grid has too many tiles
+#[cfg(not(feature = "parallel"))]
+pub fn draw_hex_grid(
+    pixmap: &mut Pixmap,
+    grid: &HexGrid,
+    color_field: &dyn Field2<Color>
+) -> Result<(), String> {
+    let hex_tile = hex_tile(grid.size, grid.orientation);
+    let tiles = visible_tiles(pixmap.width(), pixmap.height(), grid, color_field)?;
+    draw_tiles(pixmap, grid, &hex_tile, &tiles.iter().collect::<Vec<_>>(), Transform::identity());
+    Ok(())
 }
 
-pub fn draw_hex_grid<'a>(
+// rasterizes horizontal bands of the pixmap on separate threads and composites them,
+// the color field is still sampled on the calling thread
+#[cfg(feature = "parallel")]
+pub fn draw_hex_grid(
     pixmap: &mut Pixmap,
     grid: &HexGrid,
     color_field: &dyn Field2<Color>
-) {
-    let screen = bounds_for(pixmap);
-    let rect = screen.transform(grid.transform.invert().unwrap()).unwrap();
-    let hex_tile = hex_tile(grid.size);
-    let (x0, y0) = (rect.left() as i32, rect.top() as i32);
-    let (x1, y1) = (rect.right() as i32, rect.bottom() as i32);
-    for r in y0..y1 {
-        for q in x0..x1 {
-            let p = grid.position(q, r);
-            let color = color_field.at(p);
-            let mut paint = Paint::default();
-            paint.set_color(color);
-            pixmap.fill_path(
-                &hex_tile,
-                &paint,
-                FillRule::Winding,
-                grid.transform.pre_translate(p.x, p.y),
-                None,
-            );
+) -> Result<(), String> {
+    use rayon::prelude::*;
+    use tiny_skia::PixmapPaint;
+
+    let tiles = visible_tiles(pixmap.width(), pixmap.height(), grid, color_field)?;
+    let hex_tile = hex_tile(grid.size, grid.orientation);
+    let margin = grid.screen_margin();
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let band_height = height.div_ceil(rayon::current_num_threads() as u32).max(16);
+    let bands: Vec<(u32, Pixmap)> = (0..height)
+        .step_by(band_height as usize)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|top| {
+            let bottom = (top + band_height).min(height);
+            let mut band = Pixmap::new(width, bottom - top).unwrap();
+            let band_tiles: Vec<_> = tiles.iter().filter(|(p, _)| {
+                let y = grid.transform.pre_translate(p.x, p.y).ty;
+                y + margin >= top as f32 && y - margin <= bottom as f32
+            }).collect();
+            draw_tiles(&mut band, grid, &hex_tile, &band_tiles, Transform::from_translate(0.0, -(top as f32)));
+            (top, band)
+        })
+        .collect();
+    for (top, band) in bands {
+        pixmap.draw_pixmap(0, top as i32, band.as_ref(), &PixmapPaint::default(), Transform::identity(), None);
+    }
+    Ok(())
+}
+
+// one svg polygon per visible tile, in screen coordinates
+pub fn hex_grid_svg(width: u32, height: u32, grid: &HexGrid, color_field: &dyn Field2<Color>) -> Result<String, String> {
+    let corners = hex_corners(grid.size, grid.orientation);
+    let mut svg = String::new();
+    for (p, color) in visible_tiles(width, height, grid, color_field)? {
+        let transform = grid.transform.pre_translate(p.x, p.y);
+        let points: Vec<_> = corners.iter().map(|corner| {
+            let mut corner = *corner;
+            transform.map_point(&mut corner);
+            format!("{:.2},{:.2}", corner.x, corner.y)
+        }).collect();
+        let stroke = grid.stroke.map_or(String::new(), |(color, width)| {
+            format!(" stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"{:.2}\"", svg_color(color), color.alpha(), width)
+        });
+        svg += &format!(
+            "<polygon points=\"{}\" fill=\"{}\" fill-opacity=\"{:.3}\"{}/>\n",
+            points.join(" "), svg_color(color), color.alpha(), stroke,
+        );
+    }
+    Ok(svg)
+}
+
+fn svg_color(color: Color) -> String {
+    let rgba = color.to_color_u8();
+    format!("#{:02x}{:02x}{:02x}", rgba.red(), rgba.green(), rgba.blue())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(orientation: Orientation) -> HexGrid {
+        HexGrid::new(7.0, 6.0, Transform::identity(), orientation)
+    }
+
+    #[test]
+    fn axial_positions_map_back_to_their_tile() {
+        for orientation in Orientation::ALL {
+            let grid = grid(orientation);
+            for q in -6..6 {
+                for r in -6..6 {
+                    assert_eq!(grid.axial_at(grid.axial_position((q, r))), (q, r), "{:?}", orientation);
+                    // anywhere well inside the tile picks the same tile
+                    let center = grid.axial_position((q, r));
+                    let near = Point::from_xy(center.x + 2.0, center.y - 2.0);
+                    assert_eq!(grid.axial_at(near), (q, r), "{:?}", orientation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn offset_coordinates_round_trip() {
+        for orientation in Orientation::ALL {
+            let grid = grid(orientation);
+            for column in -6..6 {
+                for row in -6..6 {
+                    assert_eq!(grid.axial_to_offset(grid.offset_to_axial((column, row))), (column, row), "{:?}", orientation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hex_at_undoes_the_grid_transform() {
+        for orientation in Orientation::ALL {
+            let transform = Transform::from_scale(2.0, 2.0).post_translate(40.0, 30.0);
+            let grid = HexGrid::new(7.0, 6.0, transform, orientation);
+            let mut center = grid.position(3, -2);
+            transform.map_point(&mut center);
+            assert_eq!(grid.hex_at(center), Some((3, -2)), "{:?}", orientation);
+        }
+    }
+
+    #[test]
+    fn flat_top_corners_have_a_flat_top_edge() {
+        let size = 4.0;
+        let corners = hex_corners(size, Orientation::FlatTop);
+        for corner in corners {
+            assert!((corner.x.hypot(corner.y) - size).abs() < 1e-4);
         }
+        // a corner on each side along x and two corners sharing the top edge
+        assert!(corners.contains(&Point::from_xy(size, 0.0)) && corners.contains(&Point::from_xy(-size, 0.0)));
+        let top = corners.iter().map(|corner| corner.y).fold(f32::MAX, f32::min);
+        assert_eq!(corners.iter().filter(|corner| corner.y == top).count(), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn flat_top_columns_alternate_down_by_half_a_tile() {
+        let grid = grid(Orientation::FlatTop);
+        let (spacing, root3) = (7.0, 3.0_f32.sqrt());
+        let origin = grid.position(0, 0);
+        assert_eq!((origin.x, origin.y), (0.0, 0.0));
+        let next_column = grid.position(1, 0);
+        assert!((next_column.x - 1.5 * spacing).abs() < 1e-4);
+        assert!((next_column.y - 0.5 * root3 * spacing).abs() < 1e-4);
+        let next_row = grid.position(0, 1);
+        assert!(next_row.x.abs() < 1e-4);
+        assert!((next_row.y - root3 * spacing).abs() < 1e-4);
+        // all six neighbours are equally far apart
+        for neighbour in [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)] {
+            let center = grid.axial_position(neighbour);
+            assert!((center.x.hypot(center.y) - root3 * spacing).abs() < 1e-4, "{:?}", neighbour);
+        }
+    }
+
+    #[test]
+    fn anti_aliasing_only_adds_partial_coverage_at_tile_edges() {
+        let alphas = |anti_alias: bool| {
+            let mut pixmap = Pixmap::new(40, 40).unwrap();
+            let grid = grid(Orientation::PointyTop).with_anti_alias(anti_alias);
+            draw_hex_grid(&mut pixmap, &grid, &crate::fields::ConstantField::new(Color::WHITE)).unwrap();
+            pixmap.pixels().iter().map(|pixel| pixel.alpha()).collect::<Vec<_>>()
+        };
+        let aliased = alphas(false);
+        assert!(aliased.iter().all(|alpha| *alpha == 0 || *alpha == 255));
+        assert!(aliased.contains(&255) && aliased.contains(&0));
+        let smooth = alphas(true);
+        assert!(smooth.iter().any(|alpha| *alpha > 0 && *alpha < 255));
+        // tile interiors are solid either way
+        let solid = |alphas: &[u8]| alphas.iter().filter(|alpha| **alpha == 255).count();
+        assert!(solid(&smooth) * 10 > solid(&aliased) * 8);
+    }
+
+    #[test]
+    fn svg_has_a_hexagon_per_visible_tile() {
+        let red = crate::fields::ConstantField::new(Color::from_rgba8(255, 0, 0, 255));
+        for orientation in Orientation::ALL {
+            let grid = grid(orientation);
+            let svg = hex_grid_svg(40, 30, &grid, &red).unwrap();
+            let polygons: Vec<&str> = svg.lines().collect();
+            assert_eq!(polygons.len(), visible_tiles(40, 30, &grid, &red).unwrap().len());
+            assert!(polygons.iter().all(|polygon| polygon.starts_with("<polygon ") && polygon.contains("fill=\"#ff0000\"")));
+            assert!(polygons.iter().all(|polygon| polygon.split(' ').filter(|word| word.contains(',')).count() == 6));
+        }
+        // one big tile covers the whole screen
+        let big = HexGrid::new(100.0, 90.0, Transform::from_translate(5.0, 5.0), Orientation::PointyTop);
+        assert_eq!(hex_grid_svg(10, 10, &big, &red).unwrap().lines().count(), 1);
+        // transparent tiles are left out
+        let clear = crate::fields::ConstantField::new(Color::TRANSPARENT);
+        assert_eq!(hex_grid_svg(40, 30, &grid(Orientation::FlatTop), &clear).unwrap(), "");
+    }
+}
@@ -0,0 +1,84 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{LazyLock, Mutex}, time::SystemTime};
+
+use tiny_skia::Pixmap;
+
+struct CacheEntry {
+    modified: Option<SystemTime>,
+    image: Result<Pixmap, String>,
+}
+
+// decoded images keyed by path, decoded again when the file changes on disk
+static CACHE: LazyLock<Mutex<HashMap<PathBuf, CacheEntry>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn decode(path: &Path) -> Result<Pixmap, String> {
+    // png stays on the fast path of tiny-skia
+    #[cfg(feature = "image")]
+    if !path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png")) {
+        return decode_any(path).map_err(|error| format!("could not load {}: {}", path.display(), error));
+    }
+    Pixmap::load_png(path).map_err(|error| format!("could not load {}: {}", path.display(), error))
+}
+
+// decodes any format the image crate knows, picked by magic bytes and then extension
+#[cfg(feature = "image")]
+fn decode_any(path: &Path) -> Result<Pixmap, String> {
+    let image = image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|error| error.to_string())?
+        .decode()
+        .map_err(|error| error.to_string())?
+        .into_rgba8();
+    let mut pixmap = Pixmap::new(image.width(), image.height()).ok_or("empty image")?;
+    for (pixel, rgba) in pixmap.pixels_mut().iter_mut().zip(image.pixels()) {
+        let [r, g, b, a] = rgba.0;
+        *pixel = tiny_skia::ColorU8::from_rgba(r, g, b, a).premultiply();
+    }
+    Ok(pixmap)
+}
+
+// looks at the cached image, only decoding it the first time or after it was modified
+fn with_image<T>(path: &Path, f: impl FnOnce(&Result<Pixmap, String>) -> T) -> T {
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let mut cache = CACHE.lock().unwrap();
+    let entry = cache.get(path).filter(|entry| entry.modified == modified);
+    if let Some(entry) = entry {
+        return f(&entry.image);
+    }
+    let image = decode(path);
+    let result = f(&image);
+    // missing files are not cached so they are picked up once they appear
+    if modified.is_some() {
+        cache.insert(path.to_path_buf(), CacheEntry { modified, image });
+    }
+    result
+}
+
+pub(crate) fn load(path: &Path) -> Result<Pixmap, String> {
+    with_image(path, Clone::clone)
+}
+
+// why the image can not be loaded, without copying it when it can
+pub(crate) fn error(path: &Path) -> Option<String> {
+    with_image(path, |image| image.as_ref().err().cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_an_image_and_reports_missing_ones() {
+        let path = std::env::temp_dir().join(format!("pixellab-image-{}.png", std::process::id()));
+        let mut pixmap = Pixmap::new(3, 2).unwrap();
+        pixmap.fill(tiny_skia::Color::from_rgba8(0, 0, 255, 255));
+        pixmap.save_png(&path).unwrap();
+
+        assert_eq!(error(&path), None);
+        assert_eq!(load(&path).unwrap().data(), pixmap.data());
+        std::fs::remove_file(&path).unwrap();
+
+        let error = error(&path).unwrap();
+        assert!(error.starts_with("could not load"), "{}", error);
+        assert!(load(&path).is_err());
+    }
+}
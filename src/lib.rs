@@ -1,15 +1,19 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
-pub use app::PixelLab;
+pub use app::{evaluate_timeline, render_timeline, PixelLab};
 
 mod fields;
+mod filters;
 mod tweening;
 
 mod hex;
+mod images;
+mod palette;
 
 mod time;
 mod nodes {
     pub mod node;
     pub mod bezier;
+    pub mod spline;
 }
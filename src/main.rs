@@ -1,11 +1,59 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+// Renders a timeline to png frames without starting the ui:
+// pixellab render input.json [--out frames/] [--fps 30] [--width 320 --height 200]
+#[cfg(not(target_arch = "wasm32"))]
+fn render(args: &[String]) -> Result<(), String> {
+    let usage = "usage: pixellab render input.json [--out frames/] [--fps 30] [--width 320 --height 200]";
+    let mut input = None;
+    let mut out = "frames".to_string();
+    let mut fps = None;
+    let (mut width, mut height) = (None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = args.next().ok_or(usage)?.clone(),
+            "--fps" => {
+                let value = args.next().ok_or(usage)?;
+                fps = Some(value.parse::<f32>().map_err(|_| format!("invalid fps: {}", value))?);
+            },
+            "--width" | "--height" => {
+                let value = args.next().ok_or(usage)?;
+                let pixels = value.parse::<usize>().map_err(|_| format!("invalid {}: {}", &arg[2..], value))?;
+                if arg == "--width" { width = Some(pixels) } else { height = Some(pixels) }
+            },
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(usage.into()),
+        }
+    }
+    let input = input.ok_or(usage)?;
+    // the size is given as a pair
+    let resolution = match (width, height) {
+        (Some(width), Some(height)) => Some([width, height]),
+        (None, None) => None,
+        _ => return Err(usage.into()),
+    };
+    let frame_count = pixellab::render_timeline(input.as_ref(), out.clone().into(), fps, resolution)
+        .map_err(|error| format!("could not render {}: {}", input, error))?;
+    println!("wrote {} frames to {}", frame_count, out);
+    Ok(())
+}
+
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render") {
+        if let Err(error) = render(&args[2..]) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([640.0, 480.0])
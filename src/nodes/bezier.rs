@@ -1,4 +1,7 @@
-use egui::{Painter, Pos2};
+use egui::{Painter, Pos2, Vec2};
+
+// polyline samples used to measure arc length
+pub const ARCLEN_SAMPLES: usize = 64;
 
 #[derive(Debug)]
 pub struct Bezier(Pos2, Pos2, Pos2, Pos2);
@@ -16,8 +19,129 @@ impl Bezier {
                 + t.powi(3) * self.3.to_vec2()
             )
     }
+    // first derivative with respect to t
+    pub fn derivative(&self, t: f32) -> Vec2 {
+        3.0 * (1.0 - t).powi(2) * (self.1 - self.0)
+            + 6.0 * (1.0 - t) * t * (self.2 - self.1)
+            + 3.0 * t.powi(2) * (self.3 - self.2)
+    }
+    // unit direction of travel, falling back to the chord where the derivative vanishes
+    pub fn tangent(&self, t: f32) -> Vec2 {
+        let derivative = self.derivative(t);
+        if derivative.length_sq() > f32::EPSILON {
+            derivative.normalized()
+        } else {
+            (self.3 - self.0).normalized()
+        }
+    }
+    // cumulative length of a polyline through evenly spaced t
+    fn length_table(&self, samples: usize) -> Vec<f32> {
+        let samples = samples.max(1);
+        let mut table = Vec::with_capacity(samples + 1);
+        let mut length = 0.0;
+        let mut previous = self.eval(0.0);
+        table.push(length);
+        for i in 1..=samples {
+            let point = self.eval(i as f32 / samples as f32);
+            length += previous.distance(point);
+            table.push(length);
+            previous = point;
+        }
+        table
+    }
+    // approximate arc length, measured along a polyline through samples + 1 points
+    pub fn length(&self, samples: usize) -> f32 {
+        *self.length_table(samples).last().unwrap()
+    }
+    // curve parameter at a fraction 0-1 of the arc length
+    pub fn t_at_arclen(&self, s: f32, samples: usize) -> f32 {
+        let table = self.length_table(samples);
+        let total = table[table.len() - 1];
+        if total <= 0.0 {
+            return s.clamp(0.0, 1.0);
+        }
+        let target = s.clamp(0.0, 1.0) * total;
+        let segment = table.partition_point(|length| *length < target).clamp(1, table.len() - 1);
+        let (start, end) = (table[segment - 1], table[segment]);
+        let fraction = if end > start { (target - start) / (end - start) } else { 0.0 };
+        (segment as f32 - 1.0 + fraction) / (table.len() - 1) as f32
+    }
+    // point at a fraction 0-1 of the arc length, so evenly spaced s move at constant speed
+    pub fn eval_by_arclen(&self, s: f32) -> Pos2 {
+        self.eval(self.t_at_arclen(s, ARCLEN_SAMPLES))
+    }
 }
 
 pub fn draw(painter: &mut Painter) {
     
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // distances between points at evenly spaced parameters
+    fn steps(curve: &Bezier, t: impl Fn(f32) -> f32) -> Vec<f32> {
+        let points: Vec<Pos2> = (0..=20).map(|i| curve.eval(t(i as f32 / 20.0))).collect();
+        points.windows(2).map(|pair| pair[0].distance(pair[1])).collect()
+    }
+
+    #[test]
+    fn evenly_spaced_arc_length_moves_at_constant_speed() {
+        // control points bunched at the start make plain t slow there
+        let curve = Bezier::new(Pos2::new(0.0, 0.0), Pos2::new(1.0, 0.0), Pos2::new(2.0, 0.0), Pos2::new(100.0, 50.0));
+        let spread = |steps: Vec<f32>| {
+            let (min, max) = steps.iter().fold((f32::MAX, 0.0_f32), |(min, max), step| (min.min(*step), max.max(*step)));
+            max / min
+        };
+        assert!(spread(steps(&curve, |t| t)) > 2.0);
+        assert!(spread(steps(&curve, |s| curve.t_at_arclen(s, 64))) < 1.05);
+    }
+
+    #[test]
+    fn straight_curves_measure_and_space_evenly() {
+        // collinear control points bunched towards the start
+        let line = Bezier::new(Pos2::new(0.0, 0.0), Pos2::new(0.6, 0.8), Pos2::new(1.2, 1.6), Pos2::new(30.0, 40.0));
+        assert!((line.length(64) - 50.0).abs() < 1e-3);
+        // plain t lags behind the middle
+        assert!(line.eval(0.5).distance(Pos2::new(15.0, 20.0)) > 5.0);
+        let points: Vec<Pos2> = (0..=10).map(|i| line.eval_by_arclen(i as f32 / 10.0)).collect();
+        for (i, point) in points.iter().enumerate() {
+            assert!(point.distance(Pos2::new(3.0, 4.0) * i as f32) < 0.1, "{} {:?}", i, point);
+        }
+    }
+
+    #[test]
+    fn arc_length_keeps_the_end_points() {
+        let curve = Bezier::new(Pos2::new(0.0, 0.0), Pos2::new(0.0, 10.0), Pos2::new(10.0, 10.0), Pos2::new(10.0, 0.0));
+        assert_eq!(curve.t_at_arclen(0.0, 64), 0.0);
+        assert!((curve.t_at_arclen(1.0, 64) - 1.0).abs() < 1e-6);
+        assert!((curve.t_at_arclen(2.0, 64) - 1.0).abs() < 1e-6);
+        // a curve without length falls back to t
+        let point = Bezier::new(Pos2::new(3.0, 3.0), Pos2::new(3.0, 3.0), Pos2::new(3.0, 3.0), Pos2::new(3.0, 3.0));
+        assert_eq!(point.t_at_arclen(0.25, 64), 0.25);
+    }
+
+    #[test]
+    fn derivative_matches_finite_differences() {
+        let curve = Bezier::new(Pos2::new(0.0, 0.0), Pos2::new(0.0, 10.0), Pos2::new(10.0, 10.0), Pos2::new(10.0, 0.0));
+        let h = 1e-3;
+        for t in [0.1, 0.5, 0.8] {
+            let estimate = (curve.eval(t + h) - curve.eval(t - h)) / (2.0 * h);
+            assert!((curve.derivative(t) - estimate).length() < 0.05, "{:?} {:?}", curve.derivative(t), estimate);
+        }
+        // the ends point along the control polygon
+        assert_eq!(curve.derivative(0.0), Vec2::new(0.0, 30.0));
+        assert_eq!(curve.derivative(1.0), Vec2::new(0.0, -30.0));
+    }
+
+    #[test]
+    fn tangent_is_a_unit_vector_with_a_chord_fallback() {
+        let curve = Bezier::new(Pos2::new(0.0, 0.0), Pos2::new(0.0, 10.0), Pos2::new(10.0, 10.0), Pos2::new(10.0, 0.0));
+        assert!((curve.tangent(0.3).length() - 1.0).abs() < 1e-5);
+        assert_eq!(curve.tangent(0.5), Vec2::new(1.0, 0.0));
+        // coinciding control points stall the curve at its start
+        let stalled = Bezier::new(Pos2::new(0.0, 0.0), Pos2::new(0.0, 0.0), Pos2::new(4.0, 3.0), Pos2::new(4.0, 3.0));
+        assert_eq!(stalled.tangent(0.0), Vec2::new(0.8, 0.6));
+    }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
-use egui::{Color32, Context, Id, Pos2, Rect, Response, Sense, Stroke, Vec2};
+use egui::{collapsing_header::CollapsingState, emath::TSTransform, Align2, Color32, Context, FontId, Id, LayerId, Order, PointerButton, Pos2, Rect, Response, Sense, Stroke, TextureHandle, Vec2};
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 pub enum PinDirection {
     Input,
     Output,
@@ -16,6 +17,7 @@ impl PinDirection {
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 pub struct PinId {
     pub node_index: usize,
     pub pin_index: usize,
@@ -33,13 +35,57 @@ impl PinId {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PinType {
+    Float,
+    Vec2,
+    String,
+    Color,
+    Transform,
+    Pixmap,
+    ColorField,
+    Palette,
+    Any,
+}
+
+impl PinType {
+    // whether an input pin of this type can be fed from an output pin of the other type
+    pub fn accepts(&self, output: &PinType) -> bool {
+        match (self, output) {
+            (PinType::Any, _) | (_, PinType::Any) => true,
+            // colors and pixmaps are converted into color fields on evaluation
+            (PinType::ColorField, PinType::Color | PinType::Pixmap) => true,
+            (input, output) => input == output,
+        }
+    }
+    // color used to draw pins and the links leaving them
+    pub fn color(&self) -> Color32 {
+        match self {
+            PinType::Float => Color32::from_rgb(96, 200, 96),
+            PinType::Vec2 => Color32::from_rgb(64, 200, 200),
+            PinType::String => Color32::from_rgb(200, 200, 200),
+            PinType::Color => Color32::from_rgb(230, 200, 64),
+            PinType::Transform => Color32::from_rgb(80, 140, 230),
+            PinType::Pixmap => Color32::from_rgb(170, 100, 220),
+            PinType::ColorField => Color32::from_rgb(230, 130, 64),
+            PinType::Palette => Color32::from_rgb(220, 100, 150),
+            PinType::Any => Color32::LIGHT_GRAY,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Pin {
+    pub pin_type: PinType,
+    pub name: Option<String>,
 }
 
 impl Pin {
-    pub(crate) fn new() -> Self {
-        Self { }
+    pub(crate) fn new(pin_type: PinType) -> Self {
+        Self { pin_type, name: None }
+    }
+    pub(crate) fn named(name: &str, pin_type: PinType) -> Self {
+        Self { pin_type, name: Some(name.into()) }
     }
 }
 
@@ -48,6 +94,14 @@ pub trait NodeWidget {
     fn out_pins(&self) -> Vec<Pin>;
     fn title(&self) -> String;
     fn ui(&mut self, ui: &mut egui::Ui) -> Response;
+    // background nodes are kept behind all other nodes
+    fn in_background(&self) -> bool {
+        false
+    }
+    // whether the user can delete the node
+    fn removable(&self) -> bool {
+        true
+    }
 }
 
 fn pin_position(rect: &Rect, pin_index: usize, direction: PinDirection) -> Pos2 {
@@ -59,9 +113,37 @@ fn pin_position(rect: &Rect, pin_index: usize, direction: PinDirection) -> Pos2
     Pos2::new(x, y)
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node<W: NodeWidget> {
+    pub widget: W,
+    // top left corner of the window on the canvas
+    pub position: Pos2,
+    // only the title bar and pins are shown
+    pub collapsed: bool,
+    // passes an input through instead of evaluating
+    pub muted: bool,
+    // latest image output, shown in the node
+    #[cfg_attr(feature = "ron", serde(skip))]
+    pub thumbnail: Option<TextureHandle>,
+    #[cfg_attr(feature = "ron", serde(skip))]
+    pub selected: bool,
+}
+
+impl<W: NodeWidget> Node<W> {
+    pub fn new(widget: W, position: Pos2) -> Self {
+        Self { widget, position, collapsed: false, muted: false, thumbnail: None, selected: false }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 pub struct Graph<W: NodeWidget> {
-    pub nodes: Vec<W>,
+    pub nodes: Vec<Node<W>>,
     pub links: Vec<(PinId, PinId)>,
+    // view transform of the canvas
+    pub pan: Vec2,
+    pub zoom: f32,
 }
 
 fn disconnect_pin(links: &mut Vec<(PinId, PinId)>, pin_id: &PinId) -> bool {
@@ -71,22 +153,46 @@ fn disconnect_pin(links: &mut Vec<(PinId, PinId)>, pin_id: &PinId) -> bool {
     after < before
 }
 
-fn pins_ui(pins: &Vec<Pin>, direction: PinDirection, links: &mut Vec<(PinId, PinId)>, node_index: usize, node_rect: &Rect, ui: &egui::Ui, radius: f32) {
+// checks that two pins can be linked, regardless of which end the drag started from
+fn can_link(a: &(PinId, Rect, PinType), b: &(PinId, Rect, PinType)) -> bool {
+    let ((from, _, from_type), (_, _, to_type)) = match a.0.direction {
+        PinDirection::Input => (b, a),
+        PinDirection::Output => (a, b),
+    };
+    from.direction == PinDirection::Output && to_type.accepts(from_type)
+}
+
+// a change to the links made by dragging between pins
+enum LinkEdit {
+    Disconnect(PinId),
+    // an input can only be fed from a single output, so this replaces any link to the input
+    Connect(PinId, PinId),
+}
+
+// returns the pin under the pointer, if any, and how the links should change
+fn pins_ui(links: &[(PinId, PinId)], all_pins: &[(PinId, Rect, PinType)], node_index: usize, ui: &egui::Ui) -> (Option<PinId>, Option<LinkEdit>) {
     let painter = ui.painter();
-    for (pin_index, pin) in pins.iter().enumerate() {
-        let center = pin_position(node_rect, pin_index, direction);
-        painter.circle_filled(center, radius, Color32::LIGHT_BLUE);
+    let mut hovered_pin = None;
+    let mut edit = None;
+    for this_pin in all_pins.iter().filter(|(pin_id, _, _)| pin_id.node_index == node_index) {
+        let (pin_id, pin_rect, pin_type) = *this_pin;
+        let direction = pin_id.direction;
+        let center = pin_rect.center();
+        painter.circle_filled(center, 0.5 * pin_rect.width(), pin_type.color());
         
-        let pin_rect = Rect::from_center_size(center, Vec2::splat(2.0 * radius));
-        let pin_id = PinId { node_index, pin_index, direction};
         let response = ui.interact(pin_rect, pin_id.id(ui), Sense::drag());
+        if response.hovered() && !response.dragged() {
+            hovered_pin = Some(pin_id);
+        }
         
         if response.drag_started() {
             // disconnect if input  pin
             if pin_id.direction == PinDirection::Input {
-                if !disconnect_pin(links, &pin_id) {
+                if links.iter().any(|(_, to)| *to == pin_id) {
+                    edit = Some(LinkEdit::Disconnect(pin_id));
+                } else {
                     response.dnd_set_drag_payload(pin_id);
-                }    
+                }
             } else {
                 response.dnd_set_drag_payload(pin_id);
             }
@@ -94,105 +200,337 @@ fn pins_ui(pins: &Vec<Pin>, direction: PinDirection, links: &mut Vec<(PinId, Pin
 
         if response.dragged() {
             if let Some(pointer) = response.interact_pointer_pos() {
+                // show a red line while hovering a pin that can't be linked to
+                let hovered = all_pins
+                    .iter()
+                    .find(|(other, rect, _)| other.direction == *direction.opposite() && rect.contains(pointer));
+                let color = match hovered {
+                    Some(other) if !can_link(this_pin, other) => Color32::RED,
+                    _ => pin_type.color(),
+                };
                 let mut lines = Vec::new();
                 lines.push(center);
                 lines.push(pointer);
-                painter.line(lines, Stroke::new(2.0, Color32::WHITE));
+                painter.line(lines, Stroke::new(2.0, color));
             }
         }
-        if let Some(link_from) = response.dnd_release_payload() {
-            links.push(pin_id.link(*link_from));
+        if let Some(link_from) = response.dnd_release_payload::<PinId>() {
+            let other = all_pins.iter().find(|(other, _, _)| *other == *link_from);
+            if let Some(other) = other.filter(|other| can_link(this_pin, other)) {
+                let (from, to) = pin_id.link(other.0);
+                edit = Some(LinkEdit::Connect(from, to));
+            }
         }
     }
+    (hovered_pin, edit)
 }
 
 impl<W: NodeWidget> Graph<W> {
     pub fn new() -> Self {
-        Self { nodes: Vec::new(), links: Vec::new() }
+        Self::with_view(Vec::new(), Vec::new(), Vec2::ZERO, 1.0)
+    }
+    pub fn with_view(nodes: Vec<Node<W>>, links: Vec<(PinId, PinId)>, pan: Vec2, zoom: f32) -> Self {
+        Self { nodes, links, pan, zoom }
+    }
+    // canvas position for the next node, cascading so new nodes don't cover each other
+    pub fn next_position(&self) -> Pos2 {
+        let step = (self.nodes.len() % 16) as f32;
+        Pos2::new(32.0 + 24.0 * step, 32.0 + 24.0 * step)
+    }
+    pub fn add_node(&mut self, widget: W) {
+        let position = self.next_position();
+        self.nodes.push(Node::new(widget, position));
+    }
+    // adds a node with its window at the given screen position
+    pub fn add_node_at(&mut self, widget: W, position: Pos2) {
+        let position = TSTransform::new(self.pan, self.zoom).inverse() * position;
+        self.nodes.push(Node::new(widget, position));
+    }
+    // scroll to zoom around the pointer, middle-drag to pan
+    fn navigate(&mut self, ctx: &Context, response: &Response) {
+        if response.dragged_by(PointerButton::Middle) {
+            self.pan += response.drag_delta();
+        }
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ctx.input(|i| i.smooth_scroll_delta.y);
+            let zoom = (self.zoom * (scroll * 0.002).exp()).clamp(0.25, 4.0);
+            // keep the canvas point under the pointer fixed
+            let local = (pointer.to_vec2() - self.pan) / self.zoom;
+            self.pan = pointer.to_vec2() - local * zoom;
+            self.zoom = zoom;
+        }
+    }
+    // overview of all nodes in the bottom right corner, click or drag to move the view
+    fn minimap(&mut self, ctx: &Context, canvas: Rect, node_rects: &[Rect]) {
+        let id = Id::new("minimap");
+        let mut visible = ctx.data_mut(|data| *data.get_persisted_mut_or(id, true));
+        let size = Vec2::new(160.0, 120.0);
+        let margin = 8.0;
+        let height = if visible { size.y + 24.0 } else { 24.0 };
+        let position = canvas.right_bottom() - Vec2::new(size.x + margin, height + margin);
+        egui::Area::new(id).order(Order::Foreground).fixed_pos(position).show(ctx, |ui| {
+            ui.checkbox(&mut visible, "minimap");
+            if !visible {
+                return;
+            }
+            let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+            let to_screen = TSTransform::new(self.pan, self.zoom);
+            let viewport = to_screen.inverse() * canvas;
+            let bounds = node_rects.iter().fold(viewport, |bounds, node_rect| bounds.union(*node_rect));
+            // fit the bounds inside the minimap keeping the aspect ratio
+            let scale = (size.x / bounds.width()).min(size.y / bounds.height());
+            let offset = rect.center().to_vec2() - bounds.center().to_vec2() * scale;
+            let to_minimap = TSTransform::new(offset, scale);
+
+            for node_rect in node_rects {
+                painter.rect_filled(to_minimap * *node_rect, 0.0, ui.visuals().widgets.inactive.bg_fill);
+            }
+            painter.rect_stroke(to_minimap * viewport, 0.0, ui.visuals().selection.stroke, egui::StrokeKind::Inside);
+
+            if let Some(pointer) = response.interact_pointer_pos() {
+                // center the view on the clicked canvas point
+                let target = to_minimap.inverse() * pointer;
+                self.pan = canvas.center().to_vec2() - target.to_vec2() * self.zoom;
+            }
+        });
+        ctx.data_mut(|data| data.insert_persisted(id, visible));
     }
-    pub fn show(&mut self, ctx: &Context, ui: &mut egui::Ui) -> egui::Response {
+    // inspect fills the tooltip of a hovered pin
+    // also returns a copy of the graph from right before the first structural edit of this frame, for undo
+    pub fn show(&mut self, ctx: &Context, ui: &mut egui::Ui, inspect: impl FnOnce(&mut egui::Ui, &Self, PinId)) -> (egui::Response, Option<Self>) where W: Clone {
         let sense = Sense::drag();
-        let (rect, response) = ui.allocate_at_least(ui.available_size(), sense);
+        let (_, response) = ui.allocate_at_least(ui.available_size(), sense);
+        self.navigate(ctx, &response);
+        let mut before = None;
+        let to_screen = TSTransform::new(self.pan, self.zoom);
 
         let mut node_rects = Vec::new();
+        let mut node_layers = Vec::new();
         let mut closed_indices = Vec::new();
+        let mut foreground_layers = Vec::new();
+        // layer under a press of the primary button, from the previous frame
+        let pressed_layer = ctx.input(|i| i.pointer.interact_pos().filter(|_| i.pointer.primary_pressed()))
+            .map(|pointer| (pointer, ctx.layer_id_at(pointer)));
+        let mut pressed_node = None;
         for (node_index, node) in self.nodes.iter_mut().enumerate() {
-            let frame = egui::Frame::group(ui.style()).fill(ui.style().visuals.panel_fill);
-            let title = egui::RichText::from(node.title()).size(12.);
-            let window = egui::Window::new(title)
-                .id(Id::new(node_index))
+            let mut frame = egui::Frame::group(ui.style()).fill(ui.style().visuals.panel_fill);
+            if node.selected {
+                frame = frame.stroke(ui.visuals().selection.stroke);
+            }
+            let mut title = egui::RichText::from(node.widget.title()).size(12.);
+            if node.muted {
+                title = title.strikethrough();
+            }
+            let id = Id::new(node_index);
+            // nodes live in canvas space, their layers are transformed into screen space
+            ctx.set_transform_layer(LayerId::new(Order::Middle, id), to_screen);
+            // the title bar is drawn by the node itself so it can hold the bypass toggle
+            let window = egui::Window::new(node.widget.title())
+                .id(id)
                 .frame(frame)
+                .title_bar(false)
+                .current_pos(node.position)
+                .constrain(false)
                 .resizable(false);
+            // mirror the node into the collapsing state of its title bar
+            let mut collapsing = CollapsingState::load_with_default_open(ctx, id.with("collapsing"), true);
+            collapsing.set_open(!node.collapsed);
             let mut is_open = true;
-            let maybe_response = window.open(&mut is_open).show(ctx, |ui| {
-                ui.set_min_size(Vec2::new(32.0, 32.0));
-                node.ui(ui);
+            let removable = node.widget.removable();
+            // only nodes with something to pass through can be bypassed
+            let bypassable = !node.widget.in_pins().is_empty() && !node.widget.out_pins().is_empty();
+            // leave room for one pin label per row
+            let rows = node.widget.in_pins().len().max(node.widget.out_pins().len());
+            let maybe_response = window.show(ctx, |ui| {
+                ui.set_min_width(64.0);
+                collapsing.show_header(ui, |ui| {
+                    ui.label(title);
+                    if bypassable {
+                        ui.toggle_value(&mut node.muted, "⏸").on_hover_text("bypass");
+                    }
+                    if removable && ui.small_button("🗙").on_hover_text("remove").clicked() {
+                        is_open = false;
+                    }
+                }).body(|ui| {
+                    ui.set_min_height(32.0_f32.max(16.0 * rows as f32));
+                    node.widget.ui(ui);
+                    if let Some(texture) = &node.thumbnail {
+                        ui.add(egui::Image::from_texture(texture).max_size(Vec2::new(96.0, 64.0)));
+                    }
+                });
             });
             if is_open {
-                let node_rect = maybe_response.unwrap().response.rect;
-                node_rects.push(node_rect);
+                let node_response = maybe_response.unwrap().response;
+                node.position = node_response.rect.min;
+                node.collapsed = CollapsingState::load(ctx, id.with("collapsing")).is_some_and(|state| !state.is_open());
+                node_rects.push(node_response.rect);
+                node_layers.push(node_response.layer_id);
+                if pressed_layer.is_some_and(|(_, layer)| layer == Some(node_response.layer_id)) {
+                    pressed_node = Some(node_index);
+                }
+                if !node.widget.in_background() {
+                    foreground_layers.push(node_response.layer_id);
+                }
             } else {
                 closed_indices.push(node_index)
             } 
         }
+        // raising every other node keeps their order among themselves
+        if self.nodes.iter().any(|node| node.widget.in_background()) {
+            for layer in foreground_layers {
+                ctx.move_to_top(layer);
+            }
+        }
+        // pressing a node selects it, shift or command adds to the selection and pressing the canvas clears it
+        if let Some((pointer, layer)) = pressed_layer {
+            let add = ctx.input(|i| i.modifiers.shift || i.modifiers.command);
+            match pressed_node {
+                Some(index) if add => self.nodes[index].selected = !self.nodes[index].selected,
+                Some(index) if !self.nodes[index].selected => {
+                    for (node_index, node) in self.nodes.iter_mut().enumerate() {
+                        node.selected = node_index == index;
+                    }
+                },
+                None if !add && layer == Some(ui.layer_id()) && response.rect.contains(pointer) => {
+                    for node in self.nodes.iter_mut() {
+                        node.selected = false;
+                    }
+                },
+                _ => {},
+            }
+        }
         closed_indices.reverse();
-        for index in closed_indices {
-            self.remove_node(index);
+        if !closed_indices.is_empty() {
+            before = Some(self.clone());
+        }
+        for index in &closed_indices {
+            self.remove_node(*index);
         }
+        // widgets may have dropped some of their pins
+        let nodes = &self.nodes;
+        self.links.retain(|(from, to)|
+            from.pin_index < nodes[from.node_index].widget.out_pins().len() &&
+            to.pin_index < nodes[to.node_index].widget.in_pins().len());
 
-        // draw links        
+        // draw links       
         for (from, to) in &self.links {
             let from_rect = &node_rects[from.node_index];
-            let from_center = pin_position(from_rect, from.pin_index, from.direction);
+            let from_center = to_screen * pin_position(from_rect, from.pin_index, from.direction);
 
             let to_rect = &node_rects[to.node_index];
-            let to_center = pin_position(to_rect, to.pin_index, to.direction);
+            let to_center = to_screen * pin_position(to_rect, to.pin_index, to.direction);
+
+            // links take the color of the pin they leave from
+            let color = self.nodes[from.node_index]
+                .widget
+                .out_pins()
+                .get(from.pin_index)
+                .map_or(Color32::WHITE, |pin| pin.pin_type.color());
 
             let mut lines = Vec::new();
             lines.push(from_center);
             lines.push(to_center);
             let painter = ui.painter();
-            painter.line(lines, Stroke::new(2.0, Color32::WHITE));
+            painter.line(lines, Stroke::new(2.0, color));
         }
 
         // pre-calculate all inputs and outputs to avoid mutable borrow woes
         let radius = 8.0;
-        let mut output_pins = Vec::new();
-        let mut input_pins = Vec::new();
+        let mut all_pins = Vec::new();
         for (node_index, (node, node_rect)) in self.nodes.iter().zip(node_rects.iter()).enumerate() {
-            for (pin_index, pin) in node.out_pins().iter().enumerate() {
-                let center = pin_position(&node_rect, pin_index, PinDirection::Output);
-                let pin_rect = Rect::from_center_size(center, Vec2::splat(2.0 * radius));
-                output_pins.push((node_index, pin_index, pin_rect));
+            // labels go on top of the node window, next to their pins
+            let label_painter = ctx.layer_painter(node_layers[node_index]);
+            let font_id = FontId::proportional(10.0);
+            let text_color = ui.visuals().text_color();
+            for (pin_index, pin) in node.widget.in_pins().iter().enumerate() {
+                if let Some(name) = &pin.name {
+                    let center = pin_position(node_rect, pin_index, PinDirection::Input);
+                    let position = center + Vec2::new(radius + 2.0, 0.0);
+                    label_painter.text(position, Align2::LEFT_CENTER, name, font_id.clone(), text_color);
+                }
             }
-            for (pin_index, pin) in node.in_pins().iter().enumerate() {
-                let center = pin_position(&node_rect, pin_index, PinDirection::Input);
-                let pin_rect = Rect::from_center_size(center, Vec2::splat(2.0 * radius));
-                input_pins.push((node_index, pin_index, pin_rect));
+            for (pin_index, pin) in node.widget.out_pins().iter().enumerate() {
+                if let Some(name) = &pin.name {
+                    let center = pin_position(node_rect, pin_index, PinDirection::Output);
+                    let position = center - Vec2::new(radius + 2.0, 0.0);
+                    label_painter.text(position, Align2::RIGHT_CENTER, name, font_id.clone(), text_color);
+                }
+            }
+
+            // pins are interacted with in screen space
+            for (pin_index, pin) in node.widget.out_pins().iter().enumerate() {
+                let center = to_screen * pin_position(node_rect, pin_index, PinDirection::Output);
+                let pin_rect = Rect::from_center_size(center, Vec2::splat(2.0 * radius * self.zoom));
+                let pin_id = PinId { node_index, pin_index, direction: PinDirection::Output };
+                all_pins.push((pin_id, pin_rect, pin.pin_type));
+            }
+            for (pin_index, pin) in node.widget.in_pins().iter().enumerate() {
+                let center = to_screen * pin_position(node_rect, pin_index, PinDirection::Input);
+                let pin_rect = Rect::from_center_size(center, Vec2::splat(2.0 * radius * self.zoom));
+                let pin_id = PinId { node_index, pin_index, direction: PinDirection::Input };
+                all_pins.push((pin_id, pin_rect, pin.pin_type));
             }
         }
 
         // draw pins
-        for (node_index, (node, node_rect)) in self.nodes.iter().zip(node_rects.iter()).enumerate() {
-            // draw input pins
-            pins_ui(&node.in_pins(), PinDirection::Input, &mut self.links, node_index, &node_rect, ui, radius);
-            pins_ui(&node.out_pins(), PinDirection::Output, &mut self.links, node_index, &node_rect, ui, radius);
+        let mut hovered_pin = None;
+        let mut link_edits = Vec::new();
+        for node_index in 0..self.nodes.len() {
+            let (hovered, edit) = pins_ui(&self.links, &all_pins, node_index, ui);
+            hovered_pin = hovered.or(hovered_pin);
+            link_edits.extend(edit);
+        }
+        if !link_edits.is_empty() && before.is_none() {
+            before = Some(self.clone());
+        }
+        for edit in link_edits {
+            match edit {
+                LinkEdit::Disconnect(pin_id) => {
+                    disconnect_pin(&mut self.links, &pin_id);
+                },
+                LinkEdit::Connect(from, to) => {
+                    disconnect_pin(&mut self.links, &to);
+                    self.links.push((from, to));
+                },
+            }
         }
-        response
+        if let Some(pin_id) = hovered_pin {
+            egui::show_tooltip_at_pointer(ctx, ui.layer_id(), Id::new("pin tooltip"), |ui| inspect(ui, self, pin_id));
+        }
+
+        self.minimap(ctx, response.rect, &node_rects);
+        (response, before)
     }
 
-    // Finds all PinIds linking to the specified node_index
-    pub fn inputs_for(&self, node_index: usize) -> Vec<PinId> {
-        let mut links: Vec<_> = self.links
-            .iter()
-            .filter(|(_, to)| (to.node_index == node_index))
-            .collect();
-        links.sort_by_key(|(_, to)| to.pin_index);
-        links.iter().map(|(from, _)| *from).collect()
+    // Finds the PinId linking to each input pin of the specified node_index, None for unconnected pins
+    pub fn inputs_for(&self, node_index: usize) -> Vec<Option<PinId>> {
+        let pin_count = self.nodes[node_index].widget.in_pins().len();
+        (0..pin_count)
+            .map(|pin_index| self.links
+                .iter()
+                .find(|(_, to)| to.node_index == node_index && to.pin_index == pin_index)
+                .map(|(from, _)| *from))
+            .collect()
     }
     
+    // removes the selected nodes that can be removed, true if any were
+    pub fn remove_selected(&mut self) -> bool {
+        let indices: Vec<usize> = (0..self.nodes.len())
+            .filter(|index| self.nodes[*index].selected && self.nodes[*index].widget.removable())
+            .collect();
+        for index in indices.iter().rev() {
+            self.remove_node(*index);
+        }
+        !indices.is_empty()
+    }
+
     fn remove_node<>(&mut self, index: usize) {
-        // fist update all links referencing a node after this
+        // first remove all links referencing this node, before other indices shift onto it
+        self.links.retain(|(from, to)| from.node_index != index && to.node_index != index);
+        // then update all links referencing a node after this
         for (from, to) in self.links.iter_mut() {
             if from.node_index > index {
                 from.node_index -= 1;
@@ -201,9 +539,52 @@ impl<W: NodeWidget> Graph<W> {
                 to.node_index -= 1;
             }
         }
-        // remove all links referencing this node
-        self.links.retain(|(from, to)| from.node_index != index && to.node_index != index);
         // finally actully remove node
         self.nodes.remove(index);
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a node with one input and one output
+    #[derive(Clone)]
+    struct Pass;
+    impl NodeWidget for Pass {
+        fn in_pins(&self) -> Vec<Pin> {
+            vec![Pin::new(PinType::Any)]
+        }
+        fn out_pins(&self) -> Vec<Pin> {
+            vec![Pin::new(PinType::Any)]
+        }
+        fn title(&self) -> String {
+            "pass".into()
+        }
+        fn ui(&mut self, ui: &mut egui::Ui) -> Response {
+            ui.label("pass")
+        }
+    }
+
+    fn link(from: usize, to: usize) -> (PinId, PinId) {
+        (
+            PinId { node_index: from, pin_index: 0, direction: PinDirection::Output },
+            PinId { node_index: to, pin_index: 0, direction: PinDirection::Input },
+        )
+    }
+
+    #[test]
+    fn removing_a_node_keeps_the_links_between_the_others() {
+        // 0 -> 1 -> 2 -> 3 and 0 -> 3
+        let mut graph = Graph::new();
+        for _ in 0..4 {
+            graph.add_node(Pass);
+        }
+        graph.links = vec![link(0, 1), link(1, 2), link(2, 3), link(0, 3)];
+        graph.nodes[1].selected = true;
+        assert!(graph.remove_selected());
+
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.links, vec![link(1, 2), link(0, 2)]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+use egui::{Pos2, Vec2};
+
+use super::bezier::Bezier;
+
+// smooth curve through all of its points, made of one cubic bezier per pair of neighbours
+#[derive(Debug)]
+pub struct CatmullRom {
+    points: Vec<Pos2>,
+}
+
+impl CatmullRom {
+    pub fn new(points: Vec<Pos2>) -> Self {
+        Self { points }
+    }
+    pub fn segments(&self) -> Vec<Bezier> {
+        let n = self.points.len();
+        // the end points are repeated so the curve starts and stops on them
+        let at = |i: isize| self.points[i.clamp(0, n as isize - 1) as usize];
+        (0..n.saturating_sub(1) as isize)
+            .map(|i| {
+                let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+                Bezier::new(p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2)
+            })
+            .collect()
+    }
+    // segment containing t and the parameter within it
+    fn locate(segments: &[Bezier], t: f32) -> (usize, f32) {
+        let u = t.clamp(0.0, 1.0) * segments.len() as f32;
+        let index = (u.floor() as usize).min(segments.len() - 1);
+        (index, u - index as f32)
+    }
+    // samples the whole spline with t in 0-1, each segment taking an equal share
+    pub fn eval(&self, t: f32) -> Pos2 {
+        let segments = self.segments();
+        if segments.is_empty() {
+            return self.points.first().copied().unwrap_or(Pos2::ZERO);
+        }
+        let (index, local) = Self::locate(&segments, t);
+        segments[index].eval(local)
+    }
+    // rate of change of eval with respect to t
+    pub fn derivative(&self, t: f32) -> Vec2 {
+        let segments = self.segments();
+        if segments.is_empty() {
+            return Vec2::ZERO;
+        }
+        let (index, local) = Self::locate(&segments, t);
+        segments[index].derivative(local) * segments.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zigzag() -> CatmullRom {
+        CatmullRom::new(vec![Pos2::new(0.0, 0.0), Pos2::new(10.0, 10.0), Pos2::new(20.0, 0.0), Pos2::new(30.0, 10.0)])
+    }
+
+    #[test]
+    fn spline_passes_through_its_points() {
+        let spline = zigzag();
+        assert_eq!(spline.eval(0.0), Pos2::new(0.0, 0.0));
+        assert!(spline.eval(1.0 / 3.0).distance(Pos2::new(10.0, 10.0)) < 1e-4);
+        assert!(spline.eval(2.0 / 3.0).distance(Pos2::new(20.0, 0.0)) < 1e-4);
+        assert_eq!(spline.eval(1.0), Pos2::new(30.0, 10.0));
+        // clamped outside 0-1
+        assert_eq!(spline.eval(-1.0), Pos2::new(0.0, 0.0));
+        assert_eq!(spline.eval(2.0), Pos2::new(30.0, 10.0));
+    }
+
+    #[test]
+    fn spline_is_smooth_where_segments_meet() {
+        let spline = zigzag();
+        let segments = spline.segments();
+        assert_eq!(segments.len(), 3);
+        for pair in segments.windows(2) {
+            assert!((pair[0].derivative(1.0) - pair[1].derivative(0.0)).length() < 1e-4);
+        }
+        // an interior point moves parallel to its neighbours' chord
+        let direction = spline.derivative(1.0 / 3.0).normalized();
+        assert!((direction - Vec2::new(1.0, 0.0)).length() < 1e-4, "{:?}", direction);
+    }
+
+    #[test]
+    fn spline_with_too_few_points() {
+        assert_eq!(CatmullRom::new(vec![]).eval(0.5), Pos2::ZERO);
+        let single = CatmullRom::new(vec![Pos2::new(3.0, 4.0)]);
+        assert_eq!(single.eval(0.5), Pos2::new(3.0, 4.0));
+        assert_eq!(single.derivative(0.5), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{LazyLock, Mutex}, time::SystemTime};
+
+use tiny_skia::Color;
+
+struct CacheEntry {
+    modified: Option<SystemTime>,
+    palette: Result<Vec<Color>, String>,
+}
+
+// parsed palettes keyed by path, parsed again when the file changes on disk
+static CACHE: LazyLock<Mutex<HashMap<PathBuf, CacheEntry>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// parses a gimp .gpl palette, or a list of hex colors with one color per line
+pub(crate) fn parse(raw: &str) -> Result<Vec<Color>, String> {
+    let mut lines = raw.lines().map(str::trim).enumerate();
+    let gimp = raw.trim_start().starts_with("GIMP Palette");
+    if gimp {
+        lines.next();
+    }
+    let mut colors = Vec::new();
+    for (number, line) in lines {
+        if line.is_empty() || line.starts_with(';') || (gimp && line.starts_with('#')) {
+            continue;
+        }
+        let color = if gimp {
+            // header fields such as "Name: ..." and "Columns: ..." carry no colors
+            if line.contains(':') {
+                continue;
+            }
+            // "r g b name", the name is optional
+            let channels: Vec<_> = line.split_whitespace().take(3).map(str::parse::<u8>).collect();
+            match channels[..] {
+                [Ok(r), Ok(g), Ok(b)] => Some(Color::from_rgba8(r, g, b, 255)),
+                _ => None,
+            }
+        } else {
+            parse_hex(line)
+        };
+        colors.push(color.ok_or_else(|| format!("line {}: could not parse \"{}\" as a color", number + 1, line))?);
+    }
+    Ok(colors)
+}
+
+// "#rrggbb", "rrggbb" or with an alpha byte appended
+fn parse_hex(text: &str) -> Option<Color> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+        return None;
+    }
+    let byte = |index: usize| u8::from_str_radix(&digits[index..index + 2], 16).ok();
+    let alpha = if digits.len() == 8 { byte(6)? } else { 255 };
+    Some(Color::from_rgba8(byte(0)?, byte(2)?, byte(4)?, alpha))
+}
+
+fn read(path: &Path) -> Result<Vec<Color>, String> {
+    std::fs::read_to_string(path)
+        .map_err(|error| error.to_string())
+        .and_then(|raw| parse(&raw))
+        .map_err(|error| format!("could not load {}: {}", path.display(), error))
+}
+
+// looks at the cached palette, only parsing it the first time or after it was modified
+fn with_palette<T>(path: &Path, f: impl FnOnce(&Result<Vec<Color>, String>) -> T) -> T {
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let mut cache = CACHE.lock().unwrap();
+    let entry = cache.get(path).filter(|entry| entry.modified == modified);
+    if let Some(entry) = entry {
+        return f(&entry.palette);
+    }
+    let palette = read(path);
+    let result = f(&palette);
+    // missing files are not cached so they are picked up once they appear
+    if modified.is_some() {
+        cache.insert(path.to_path_buf(), CacheEntry { modified, palette });
+    }
+    result
+}
+
+pub(crate) fn load(path: &Path) -> Result<Vec<Color>, String> {
+    with_palette(path, Clone::clone)
+}
+
+// how many colors the palette has, without copying them
+pub(crate) fn count(path: &Path) -> Result<usize, String> {
+    with_palette(path, |palette| palette.as_ref().map(Vec::len).map_err(Clone::clone))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(colors: &[Color]) -> Vec<[u8; 4]> {
+        colors.iter().map(|color| {
+            let color = color.to_color_u8();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        }).collect()
+    }
+
+    #[test]
+    fn parses_gimp_palettes() {
+        let raw = "GIMP Palette\nName: Sunset\nColumns: 4\n#\n# a comment\n255   0  0\tRed\n  0 128 255 Sky blue\n\n 16 16 16\n";
+        assert_eq!(rgba(&parse(raw).unwrap()), [[255, 0, 0, 255], [0, 128, 255, 255], [16, 16, 16, 255]]);
+    }
+
+    #[test]
+    fn parses_hex_lists() {
+        let raw = "; pico\n#000000\n1d2b53\n#7e255380\n";
+        assert_eq!(rgba(&parse(raw).unwrap()), [[0, 0, 0, 255], [0x1d, 0x2b, 0x53, 255], [0x7e, 0x25, 0x53, 0x80]]);
+        assert_eq!(parse("").unwrap(), []);
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_color() {
+        assert_eq!(parse("GIMP Palette\n255 0 0\n300 0 0\n").unwrap_err(), "line 3: could not parse \"300 0 0\" as a color");
+        assert_eq!(parse("#fff\n").unwrap_err(), "line 1: could not parse \"#fff\" as a color");
+        assert!(parse("#12345é\n").is_err());
+    }
+
+    #[test]
+    fn loaded_palettes_are_cached_until_the_file_changes() {
+        let path = std::env::temp_dir().join(format!("pixellab-palette-{}.hex", std::process::id()));
+        std::fs::write(&path, "#000000\n#ffffff\n").unwrap();
+        assert_eq!(count(&path), Ok(2));
+        assert_eq!(rgba(&load(&path).unwrap()), [[0, 0, 0, 255], [255, 255, 255, 255]]);
+
+        // a newer file is parsed again
+        std::fs::write(&path, "#ff0000\n").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert_eq!(count(&path), Ok(1));
+        std::fs::remove_file(&path).unwrap();
+
+        let error = count(&path).unwrap_err();
+        assert!(error.starts_with("could not load"), "{}", error);
+    }
+}
@@ -1,5 +1,7 @@
-use std::{iter::Sum, ops::Add};
+use std::{iter::Sum, ops::{Add, Sub}};
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 pub struct Duration {
     pub millis: u32,
 }
@@ -11,6 +13,16 @@ impl Duration {
         Self { millis, }
     }
     pub fn as_millis(&self) -> u32 { self.millis }
+    pub fn as_frames(&self, fps: f32) -> u32 {
+        (self.millis as f32 * fps / 1000.0) as u32
+    }
+    // formats as mm:ss:ff
+    pub fn format(&self, fps: f32) -> String {
+        let minutes = self.millis / 60_000;
+        let seconds = self.millis / 1000 % 60;
+        let frames = Duration::from_millis(self.millis % 1000).as_frames(fps);
+        format!("{:02}:{:02}:{:02}", minutes, seconds, frames)
+    }
 }
 impl Add for &Duration {
     type Output = Duration;
@@ -19,11 +31,21 @@ impl Add for &Duration {
         Duration { millis: self.millis + rhs.millis }
     }
 }
+impl Sub for &Duration {
+    type Output = Duration;
+
+    // saturates at zero
+    fn sub(self, rhs: Self) -> Self::Output {
+        Duration { millis: self.millis.saturating_sub(rhs.millis) }
+    }
+}
 impl<'a> Sum<&'a Duration> for Duration {
     fn sum<I: Iterator<Item = &'a Duration>>(iter: I) -> Duration {
         Duration::from_millis(iter.map(|d| d.millis).sum())
     }
 }
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instant {
     pub millis: u32, // TODO: should not be public probably
 }
@@ -38,4 +60,31 @@ impl Instant {
     pub(crate) fn after(&self, duration: &Duration) -> Instant {
         Self { millis: self.millis + duration.millis, }
     }
-}
\ No newline at end of file
+
+    pub(crate) fn duration_since(&self, earlier: &Instant) -> Duration {
+        Duration::from_millis(self.millis.saturating_sub(earlier.millis))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_minutes_seconds_and_frames() {
+        assert_eq!(Duration::from_millis(0).format(30.0), "00:00:00");
+        assert_eq!(Duration::from_millis(999).format(30.0), "00:00:29");
+        assert_eq!(Duration::from_millis(1000).format(30.0), "00:01:00");
+        assert_eq!(Duration::from_millis(61_500).format(30.0), "01:01:15");
+        assert_eq!(Duration::from_millis(61_500).format(24.0), "01:01:12");
+        assert_eq!(Duration::from_millis(3_599_999).format(60.0), "59:59:59");
+        // minutes keep counting past an hour
+        assert_eq!(Duration::from_millis(3_600_000).format(30.0), "60:00:00");
+    }
+
+    #[test]
+    fn counts_whole_frames() {
+        assert_eq!(Duration::from_millis(33).as_frames(30.0), 0);
+        assert_eq!(Duration::from_millis(34).as_frames(30.0), 1);
+        assert_eq!(Duration::from_secs(2.0).as_frames(25.0), 50);
+    }
+}
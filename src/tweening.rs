@@ -13,4 +13,106 @@ pub(crate) fn elastic_out(k: f32) -> f32 {
     if k == 1.0 { return 1.0; }    
     //Math.pow(2, -10 * k) * Math.sin((k - 0.1) * 5 * Math.PI) + 1;
     2.0_f32.powf(-10.0 * k) * ((k - 0.1) * 5.0 * PI).sin() + 1.0
-}
\ No newline at end of file
+}
+// maps value linearly from one range to another, an empty input range maps to out_min
+pub(crate) fn remap(value: f32, in_min: f32, in_max: f32, out_min: f32, out_max: f32) -> f32 {
+    if in_min == in_max {
+        return out_min;
+    }
+    out_min + (value - in_min) * (out_max - out_min) / (in_max - in_min)
+}
+
+// hermite interpolation between two edges, a step when the edges coincide
+pub(crate) fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge0 == edge1 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// deterministic pseudo random number in 0-1 from a seed and a salt, using splitmix64
+pub(crate) fn random(seed: u32, salt: f32) -> f32 {
+    let mut z = ((seed as u64) << 32 | salt.to_bits() as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    // the top 24 bits fit exactly in a float
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+// periodic shapes with a period of one, ranging from -1 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Waveform {
+    Sine,
+    Sawtooth,
+    Triangle,
+    Square,
+}
+impl Waveform {
+    pub const ALL: [Waveform; 4] = [Waveform::Sine, Waveform::Sawtooth, Waveform::Triangle, Waveform::Square];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Waveform::Sine => "sine",
+            Waveform::Sawtooth => "sawtooth",
+            Waveform::Triangle => "triangle",
+            Waveform::Square => "square",
+        }
+    }
+    pub fn eval(&self, phase: f32) -> f32 {
+        let phase = phase.rem_euclid(1.0);
+        match self {
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remap_maps_between_ranges() {
+        assert_eq!(remap(0.5, 0.0, 1.0, 10.0, 20.0), 15.0);
+        assert_eq!(remap(2.0, 0.0, 4.0, 1.0, -1.0), 0.0);
+        // values outside the input range extrapolate
+        assert_eq!(remap(2.0, 0.0, 1.0, 0.0, 10.0), 20.0);
+    }
+
+    #[test]
+    fn remap_empty_range_maps_to_out_min() {
+        assert_eq!(remap(3.0, 1.0, 1.0, 5.0, 9.0), 5.0);
+    }
+
+    #[test]
+    fn smoothstep_clamps_and_eases() {
+        assert_eq!(smoothstep(0.0, 1.0, -1.0), 0.0);
+        assert_eq!(smoothstep(0.0, 1.0, 2.0), 1.0);
+        assert_eq!(smoothstep(2.0, 4.0, 3.0), 0.5);
+        // slower than linear near the edges
+        assert!(smoothstep(0.0, 1.0, 0.1) < 0.1);
+        assert!(smoothstep(0.0, 1.0, 0.9) > 0.9);
+    }
+
+    #[test]
+    fn smoothstep_with_coinciding_edges_is_a_step() {
+        assert_eq!(smoothstep(1.0, 1.0, 0.5), 0.0);
+        assert_eq!(smoothstep(1.0, 1.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn random_is_deterministic_and_in_range() {
+        assert_eq!(random(7, 0.5), random(7, 0.5));
+        assert_ne!(random(7, 0.5), random(8, 0.5));
+        assert_ne!(random(7, 0.5), random(7, 1.5));
+        let values: Vec<f32> = (0..1000).map(|i| random(1, i as f32)).collect();
+        assert!(values.iter().all(|value| (0.0..1.0).contains(value)));
+        // roughly uniform
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        assert!((mean - 0.5).abs() < 0.05, "{}", mean);
+    }
+}